@@ -0,0 +1,48 @@
+/// A sensor curve, linearly interpolated between raw -> engineering value points.
+pub struct Calibration {
+    points: Vec<(f32, f32)>,
+}
+
+impl Calibration {
+    pub fn new(mut points: Vec<(f32, f32)>) -> Calibration {
+        points.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        Calibration { points }
+    }
+
+    /// Raw values outside the table are clamped to its first and last point, and NaN
+    /// is passed through.
+    pub fn apply(&self, raw: f32) -> f32 {
+        if raw.is_nan() {
+            return raw;
+        }
+        let (first, last) = match (self.points.first(), self.points.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return raw,
+        };
+        if raw <= first.0 {
+            return first.1;
+        }
+        if raw >= last.0 {
+            return last.1;
+        }
+
+        let upper = self.points.iter().position(|p| p.0 >= raw).unwrap_or(0);
+        let (raw_low, value_low) = self.points[upper - 1];
+        let (raw_high, value_high) = self.points[upper];
+        value_low + (raw - raw_low) * (value_high - value_low) / (raw_high - raw_low)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolates_and_clamps() {
+        let calibration = Calibration::new(vec![(10.0, 100.0), (0.0, 0.0)]);
+        assert_eq!(calibration.apply(2.5), 25.0);
+        assert_eq!(calibration.apply(-1.0), 0.0);
+        assert_eq!(calibration.apply(f32::INFINITY), 100.0);
+        assert!(calibration.apply(f32::NAN).is_nan());
+    }
+}
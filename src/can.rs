@@ -0,0 +1,138 @@
+use socketcan::{CANSocket, CANSocketOpenError};
+use std::io;
+use std::mem;
+use std::os::unix::io::AsRawFd;
+use std::time::Duration;
+
+/// A classic or FD frame, with up to 64 bytes of payload.
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub id: u32,
+    pub extended: bool,
+    pub data: Vec<u8>,
+}
+
+/// Matches frames by ID type and an ID, mask or range of IDs.
+#[derive(Clone, Debug)]
+pub struct FrameFilter {
+    pub extended: bool,
+    pub first_id: u32,
+    pub last_id: u32,
+    pub mask: u32,
+}
+
+impl FrameFilter {
+    pub fn exact(id: u32, extended: bool) -> FrameFilter {
+        FrameFilter {
+            extended,
+            first_id: id,
+            last_id: id,
+            mask: libc::CAN_EFF_MASK,
+        }
+    }
+
+    pub fn matches(&self, frame: &Frame) -> bool {
+        let id = frame.id & self.mask;
+        frame.extended == self.extended && id >= self.first_id && id <= self.last_id
+    }
+}
+
+/// A raw CAN socket, which optionally also receives CAN FD frames.
+pub struct CanSocket {
+    socket: CANSocket,
+}
+
+impl CanSocket {
+    pub fn open(interface: &str, fd: bool) -> Result<CanSocket, CANSocketOpenError> {
+        let socket = CANSocket::open(interface)?;
+
+        if fd {
+            let enable: libc::c_int = 1;
+            let result = unsafe {
+                libc::setsockopt(
+                    socket.as_raw_fd(),
+                    libc::SOL_CAN_RAW,
+                    libc::CAN_RAW_FD_FRAMES,
+                    &enable as *const _ as *const libc::c_void,
+                    mem::size_of::<libc::c_int>() as libc::socklen_t,
+                )
+            };
+            if result != 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+        }
+
+        Ok(CanSocket { socket })
+    }
+
+    pub fn set_read_timeout(&self, duration: Duration) -> io::Result<()> {
+        self.socket.set_read_timeout(duration)
+    }
+
+    pub fn read_frame(&self) -> io::Result<Frame> {
+        // A classic frame has the same layout as the start of an FD frame.
+        let mut frame: libc::canfd_frame = unsafe { mem::zeroed() };
+        let read = unsafe {
+            libc::read(
+                self.socket.as_raw_fd(),
+                &mut frame as *mut _ as *mut libc::c_void,
+                libc::CANFD_MTU,
+            )
+        };
+
+        let max_length = match read {
+            n if n < 0 => return Err(io::Error::last_os_error()),
+            n if n as usize == libc::CAN_MTU => libc::CAN_MAX_DLEN,
+            n if n as usize == libc::CANFD_MTU => libc::CANFD_MAX_DLEN,
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "Incomplete CAN frame")),
+        };
+        // Remote and error frames carry no data, the caller reads again.
+        if frame.can_id & (libc::CAN_RTR_FLAG | libc::CAN_ERR_FLAG) != 0 {
+            return Err(io::Error::new(io::ErrorKind::WouldBlock, "Remote or error frame"));
+        }
+
+        let extended = frame.can_id & libc::CAN_EFF_FLAG != 0;
+        let id = if extended {
+            frame.can_id & libc::CAN_EFF_MASK
+        } else {
+            frame.can_id & libc::CAN_SFF_MASK
+        };
+        let length = usize::from(frame.len).min(max_length);
+
+        Ok(Frame {
+            id,
+            extended,
+            data: frame.data[..length].to_vec(),
+        })
+    }
+
+    pub fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+        let mut raw: libc::canfd_frame = unsafe { mem::zeroed() };
+        raw.can_id = if frame.extended {
+            frame.id | libc::CAN_EFF_FLAG
+        } else {
+            frame.id
+        };
+        raw.len = frame.data.len().min(libc::CANFD_MAX_DLEN) as u8;
+        raw.data[..usize::from(raw.len)].copy_from_slice(&frame.data[..usize::from(raw.len)]);
+
+        // Frames with more than 8 bytes can only go out as FD frames.
+        let size = if frame.data.len() > libc::CAN_MAX_DLEN {
+            libc::CANFD_MTU
+        } else {
+            libc::CAN_MTU
+        };
+        let written = unsafe {
+            libc::write(
+                self.socket.as_raw_fd(),
+                &raw as *const _ as *const libc::c_void,
+                size,
+            )
+        };
+
+        if written < 0 || written as usize != size {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,200 @@
+use crate::can::Frame;
+use crate::decoder::Decoder;
+use crate::expr::Expression;
+use crate::signal::DecodeError;
+use std::collections::HashMap;
+
+struct Computed {
+    name: String,
+    expression: Expression,
+    variables: Vec<String>,
+}
+
+/// The latest value of every named channel. Channels are either decoded from
+/// frames, or computed from other channels whenever one of those changes.
+pub struct Channels {
+    inputs: Vec<(String, Decoder)>,
+    // Set directly, e.g. counts kept elsewhere.
+    set_values: Vec<String>,
+    // Sorted so that every computed channel comes after the ones it uses.
+    computed: Vec<Computed>,
+    values: HashMap<String, Result<f32, DecodeError>>,
+}
+
+impl Channels {
+    pub fn new() -> Channels {
+        Channels {
+            inputs: Vec::new(),
+            set_values: Vec::new(),
+            computed: Vec::new(),
+            values: HashMap::new(),
+        }
+    }
+
+    pub fn add_input(&mut self, name: String, decoder: Decoder) {
+        self.inputs.push((name, decoder));
+    }
+
+    /// Adds a channel whose value is given with `set`.
+    pub fn add_value(&mut self, name: String) {
+        self.set_values.push(name);
+    }
+
+    /// Adds a channel computed from others. Panics if channels use each other.
+    pub fn add_computed(&mut self, name: String, expression: Expression) {
+        let variables = expression.variables().iter().map(|v| v.to_string()).collect();
+        self.computed.push(Computed {
+            name,
+            expression,
+            variables,
+        });
+
+        // Take the computed channels in declaration order, each once all it uses is taken.
+        let mut unsorted = std::mem::take(&mut self.computed);
+        while !unsorted.is_empty() {
+            let ready = unsorted.iter().position(|computed| {
+                computed
+                    .variables
+                    .iter()
+                    .all(|v| !unsorted.iter().any(|other| &other.name == v))
+            });
+            match ready {
+                Some(ready) => self.computed.push(unsorted.remove(ready)),
+                None => {
+                    let names: Vec<&str> = unsorted.iter().map(|c| c.name.as_str()).collect();
+                    panic!("Computed channels {} use each other", names.join(", "));
+                }
+            }
+        }
+    }
+
+    /// The channels decoded from frames, and their decoders.
+    pub fn inputs(&self) -> impl Iterator<Item = (&str, &Decoder)> {
+        self.inputs.iter().map(|(name, decoder)| (name.as_str(), decoder))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.inputs.iter().any(|(input, _)| input == name)
+            || self.set_values.iter().any(|set_value| set_value == name)
+            || self.computed.iter().any(|computed| computed.name == name)
+    }
+
+    /// Names used by computed channels, which aren't channels themselves.
+    pub fn undefined(&self) -> Vec<String> {
+        let mut undefined: Vec<String> = Vec::new();
+        for computed in self.computed.iter() {
+            for variable in computed.variables.iter() {
+                if !self.contains(variable) && !undefined.contains(variable) {
+                    undefined.push(variable.clone());
+                }
+            }
+        }
+        undefined
+    }
+
+    pub fn value(&self, name: &str) -> Option<Result<f32, DecodeError>> {
+        self.values.get(name).cloned()
+    }
+
+    /// Decodes a frame, and returns the names of the channels that changed.
+    pub fn update(&mut self, frame: &Frame) -> Vec<String> {
+        self.decode(frame, false)
+    }
+
+    /// Decodes a frame taken by a transport protocol, for the channels that read
+    /// frames as they arrive, and returns the names of the channels that changed.
+    pub fn update_raw(&mut self, frame: &Frame) -> Vec<String> {
+        self.decode(frame, true)
+    }
+
+    /// Sets the value of a channel added with `add_value`, and returns the names of
+    /// the channels that changed.
+    pub fn set(&mut self, name: &str, value: Result<f32, DecodeError>) -> Vec<String> {
+        self.values.insert(name.to_string(), value);
+        self.evaluate(vec![name.to_string()])
+    }
+
+    /// Sets the channels decoded from a frame that was rejected to `error`, and
+    /// returns the names of the channels that changed.
+    pub fn fail(&mut self, frame: &Frame, error: DecodeError) -> Vec<String> {
+        let mut changed: Vec<String> = Vec::new();
+
+        for (name, decoder) in self.inputs.iter() {
+            if decoder.filter.matches(frame) {
+                self.values.insert(name.clone(), Err(error));
+                changed.push(name.clone());
+            }
+        }
+
+        self.evaluate(changed)
+    }
+
+    fn decode(&mut self, frame: &Frame, raw_only: bool) -> Vec<String> {
+        let mut changed: Vec<String> = Vec::new();
+
+        let inputs = self.inputs.iter().filter(|(_, decoder)| decoder.raw || !raw_only);
+        for (name, decoder) in inputs {
+            if let Some(value) = decoder.decode(frame) {
+                self.values.insert(name.clone(), value);
+                changed.push(name.clone());
+            }
+        }
+        self.evaluate(changed)
+    }
+
+    /// Evaluates the computed channels using any of the changed ones.
+    fn evaluate(&mut self, mut changed: Vec<String>) -> Vec<String> {
+        if changed.is_empty() {
+            return changed;
+        }
+
+        for computed in self.computed.iter() {
+            if !computed.variables.iter().any(|v| changed.contains(v)) {
+                continue;
+            }
+            let values = &self.values;
+            if let Some(value) = computed.expression.evaluate(&|name| values.get(name).cloned()) {
+                self.values.insert(computed.name.clone(), value);
+                changed.push(computed.name.clone());
+            }
+        }
+
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn computed(channels: &mut Channels, name: &str, expression: &str) {
+        channels.add_computed(name.to_string(), Expression::parse(expression).unwrap());
+    }
+
+    #[test]
+    fn evaluates_channels_declared_before_their_inputs() {
+        let mut channels = Channels::new();
+        channels.add_value("speed".to_string());
+        computed(&mut channels, "doubled", "halved * 4");
+        computed(&mut channels, "halved", "speed / 2");
+
+        let changed = channels.set("speed", Ok(10.0));
+        assert_eq!(changed, vec!["speed", "halved", "doubled"]);
+        assert_eq!(channels.value("doubled"), Some(Ok(20.0)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Computed channels a, b use each other")]
+    fn rejects_cycles() {
+        let mut channels = Channels::new();
+        computed(&mut channels, "a", "b + 1");
+        computed(&mut channels, "b", "a + 1");
+    }
+
+    #[test]
+    #[should_panic(expected = "Computed channels a use each other")]
+    fn rejects_channels_using_themselves() {
+        let mut channels = Channels::new();
+        computed(&mut channels, "a", "a + 1");
+    }
+}
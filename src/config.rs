@@ -1,39 +1,296 @@
 use serde::Deserialize;
-use half::f16;
-use std::convert::TryInto;
+use std::convert::TryFrom;
+use crate::can::FrameFilter;
+use crate::j1939;
+use crate::signal::{ByteOrder, Multiplexer, Signal, ValueType};
 
 #[derive(Deserialize)]
 pub struct Config {
-    pub interface: String,
+    pub interface: Option<String>,
+    #[serde(default)]
+    pub fd: bool, // Also receive CAN FD frames on the interface
+    pub slcan: Option<Slcan>, // Read a serial SLCAN adapter, instead of the interface
+    pub replay: Option<Replay>, // Play back a recording, instead of reading the interface
+    pub generator: Option<Generator>, // Send made up values to the gauges, instead of reading the interface
+    pub record: Option<Record>, // Write the received frames to candump log files
     pub slot_size: u8,
     pub width: u32,
     pub height: u32,
     pub gauges: Vec<Gauge>,
+    #[serde(default)]
+    pub channels: Vec<Channel>,
+    #[serde(default)]
+    pub dbc_files: Vec<String>,
+    pub obd: Option<Obd>,
+    #[serde(default)]
+    pub isotp: Vec<IsoTpLink>,
+    #[serde(default)]
+    pub frame_checks: Vec<FrameCheckConfig>,
+    pub peak_reset_key: Option<String>, // Key that resets held peaks, "R" by default
+    pub peak_reset_channel: Option<String>, // Resets held peaks when it becomes non-zero
 
     #[cfg(feature = "colors")]
     pub colors: Colors,
 }
 
+/// A USB-serial CAN adapter speaking the SLCAN (Lawicel) protocol.
+#[derive(Deserialize)]
+pub struct Slcan {
+    pub device: String, // e.g. "/dev/ttyACM0"
+    pub bitrate: u32, // CAN bitrate in bit/s, from 10000 to 1000000
+    pub baud: Option<u32>, // Serial baud rate, 115200 by default
+}
+
+/// A recorded log, played back with its original timing.
+#[derive(Deserialize)]
+pub struct Replay {
+    pub file: String,
+    pub format: Option<TraceFormat>, // Guessed from the file extension by default
+    pub csv: Option<CsvLayout>, // Columns of a CSV file
+    pub speed: Option<f32>, // 2.0 plays back twice as fast
+    #[serde(default)]
+    pub looping: bool, // Start over at the end of the log
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+pub enum TraceFormat {
+    Candump, // Written by `candump -l`
+    Asc, // Vector ASCII log
+    Csv,
+}
+
+/// Which columns of a CSV trace hold what, all 1-indexed.
+#[derive(Deserialize)]
+pub struct CsvLayout {
+    pub timestamp: usize,
+    pub id: usize, // Hex, extended when above 0x7FF or ending in x
+    pub dlc: Option<usize>, // Data length, else the data runs to the end of the row
+    pub data: usize, // Hex bytes, space separated or one per column from here on
+    pub delimiter: Option<char>, // "," by default
+    pub header_lines: Option<usize>, // Skipped, 1 by default
+    pub timestamp_scale: Option<f64>, // Seconds per timestamp unit, e.g. 0.001 for ms
+}
+
+/// Makes up values for the gauges, to try out a layout without a CAN bus.
+#[derive(Deserialize)]
+pub struct Generator {
+    pub rate: Option<f32>, // Frames per second, 20 by default
+}
+
+/// What the generator sends for a gauge, e.g. `{ type = "Square", period = 2.0 }`.
+/// Waveforms span the gauge's range, periods and durations are in seconds.
+#[derive(Deserialize, Clone)]
+#[serde(tag = "type")]
+pub enum Waveform {
+    Sine { period: Option<f32> },
+    Ramp { period: Option<f32> },
+    Square { period: Option<f32>, duty: Option<f32> }, // Fraction of the period spent high, 0.5 by default
+    RandomWalk { step: Option<f32> }, // Largest change per frame, as a fraction of the range
+    Steps { values: Vec<f32>, duration: Option<f32> }, // Each value is held for the duration
+}
+
+/// Where and what to record, and when to start a new file.
+#[derive(Deserialize)]
+pub struct Record {
+    pub directory: Option<String>, // The working directory by default
+    pub frame_ids: Option<Vec<u32>>, // Only record these frames
+    pub extended: Option<bool>, // Of the frame_ids, defaults to extended for IDs above 0x7FF
+    pub max_size: Option<u64>, // Bytes per file
+    pub max_duration: Option<f32>, // Seconds per file
+}
+
+/// Where a value is read from, and how it is decoded.
+#[derive(Deserialize)]
+pub struct Source {
+    pub frame_id: Option<u32>,
+    pub extended: Option<bool>, // Defaults to extended for IDs above 0x7FF
+    pub frame_mask: Option<u32>,
+    pub frame_id_range: Option<[u32; 2]>, // Inclusive, instead of frame_id
+    pub slot_id: Option<u8>, // 1-indexed
+    pub signal: Option<String>, // Message.Signal from the DBC files
+    pub data_type: Option<GaugeDataType>,
+    // Bit-level layout, takes precedence over slot_id/data_type.
+    pub start_bit: Option<u16>, // DBC numbering, MSB for big endian
+    pub bit_length: Option<u16>,
+    pub byte_order: Option<ByteOrder>,
+    pub signed: Option<bool>,
+    pub factor: Option<f32>,
+    pub offset: Option<f32>,
+    pub multiplexer: Option<MultiplexerConfig>,
+    pub calibration: Option<Vec<CalibrationPoint>>, // Applied after factor/offset
+    pub bit: Option<u8>, // Bit within a B8/B16 slot, 0 is the LSB
+    pub uds: Option<UdsDid>, // Poll the value, instead of waiting for frame_id
+    pub pgn: Option<u32>, // J1939 PGN, instead of frame_id
+    pub spn: Option<u32>, // J1939 SPN from the built-in table, instead of pgn and a layout
+    pub source_address: Option<u8>, // Only read the PGN from this J1939 node
+}
+
 #[derive(Deserialize)]
 pub struct Gauge {
-    pub frame_id: u32,
-    pub slot_id: u8, // 1-indexed
+    #[serde(flatten)]
+    pub source: Source,
+    pub name: Option<String>, // Makes the value available to channel expressions
+    pub channel: Option<String>, // Read a channel, instead of decoding a frame
     pub gauge: GaugeType,
-    pub data_type: GaugeDataType,
+    pub active_low: Option<bool>,
     pub title: String,
-    pub unit: String,
+    pub unit: Option<String>,
+    pub source_unit: Option<String>, // Unit on the wire, if it differs from the displayed unit
     pub min_value: Option<f32>,
     pub max_value: Option<f32>,
     pub indicators: Option<Vec<f32>>,
+    pub value_labels: Option<Vec<ValueLabel>>, // For LabelGauge, of the decoded value, defaults to the DBC value table
+    pub fallback_label: Option<String>, // Shown for values without a label, instead of the number
+    pub timeout: Option<f32>, // Seconds without data before the gauge shows it is stale
+    #[serde(default)]
+    pub filters: Vec<FilterConfig>, // Smoothing of the displayed value
+    pub alarm: Option<AlarmConfig>,
+    pub peak_hold: Option<PeakHold>,
+    pub waveform: Option<Waveform>, // Values made up by the generator, a sine over the gauge's range by default
+    #[serde(default)]
     pub digits: u8,
     pub point: StartPoint,
     pub size: AreaSize,
 }
 
+/// A named value, either decoded from frames or computed from other channels.
+#[derive(Deserialize)]
+pub struct Channel {
+    pub name: String,
+    pub expression: Option<String>, // e.g. "Engine.MAP - Engine.Baro"
+    #[serde(flatten)]
+    pub source: Source,
+}
+
+/// OBD-II polling, for gauges and channels reading `obd:<PID>`.
+#[derive(Deserialize)]
+pub struct Obd {
+    pub request_id: Option<u32>, // Defaults to the functional 0x7DF, or 0x7E0-0x7E7 for one ECU
+    pub default_rate: Option<f32>, // Requests per second, for PIDs not listed below
+    #[serde(default)]
+    pub pids: Vec<ObdPid>,
+}
+
+#[derive(Deserialize)]
+pub struct ObdPid {
+    pub pid: u8,
+    pub rate: f32, // Requests per second
+}
+
+/// An ISO-TP link. Its reassembled messages are decoded in place of the frames
+/// from `response_id`, so gauges on that `frame_id` see the whole message.
+#[derive(Deserialize)]
+pub struct IsoTpLink {
+    pub request_id: u32,
+    pub response_id: u32,
+    #[serde(default)]
+    pub extended: bool,
+    pub request: Option<Vec<u8>>, // Sent `rate` times per second, e.g. [0x09, 0x02] for the VIN
+    pub rate: Option<f32>, // Defaults to once per second
+}
+
+/// A UDS data identifier, read with ReadDataByIdentifier (0x22). The slots or
+/// start bits of the value count from the first data byte after the DID.
+#[derive(Deserialize)]
+pub struct UdsDid {
+    pub request_id: u32,
+    pub response_id: u32,
+    #[serde(default)]
+    pub extended: bool,
+    pub did: u16,
+    pub rate: Option<f32>, // Requests per second, defaults to 5
+    pub session: Option<u8>, // Diagnostic session to read it in, e.g. 0x03 for the extended session
+}
+
+/// Alive counter and checksum rules for a frame. Frames that fail are dropped, and
+/// the gauges reading them show a fault.
+#[derive(Deserialize)]
+pub struct FrameCheckConfig {
+    pub frame_id: u32,
+    pub extended: Option<bool>, // Defaults to extended for IDs above 0x7FF
+    pub name: Option<String>, // Channel counting the failed frames
+    pub counter: Option<CounterConfig>,
+    pub checksum: Option<ChecksumConfig>,
+}
+
+/// A counter that increments by one with every frame, wrapping at its bit length.
+#[derive(Deserialize)]
+pub struct CounterConfig {
+    pub start_bit: u16,
+    pub bit_length: u16,
+    pub byte_order: Option<ByteOrder>,
+}
+
+/// A checksum byte, calculated over all other bytes of the frame.
+#[derive(Deserialize)]
+pub struct ChecksumConfig {
+    pub algorithm: ChecksumAlgorithm,
+    pub byte: u8, // 0-indexed
+    // CRC8 parameters, SAE J1850 by default.
+    pub polynomial: Option<u8>,
+    pub init: Option<u8>,
+    pub xor_out: Option<u8>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub enum ChecksumAlgorithm {
+    Xor,
+    Crc8,
+}
+
+#[derive(Deserialize)]
+pub struct MultiplexerConfig {
+    pub start_bit: u16,
+    pub bit_length: Option<u16>, // Defaults to a mux byte
+    pub byte_order: Option<ByteOrder>,
+    pub value: u64,
+}
+
+#[derive(Deserialize)]
+pub struct ValueLabel {
+    pub value: i64,
+    pub label: String,
+}
+
+/// Smoothing of a gauge's values, e.g. `{ type = "Ema", alpha = 0.2 }`.
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+pub enum FilterConfig {
+    Ema { alpha: f32 }, // Weight of each new value, from 0 to 1
+    MovingAverage { samples: usize },
+    Median { samples: usize },
+    SlewRate { rate: f32 }, // Largest change per second
+}
+
+/// Limits outside of which a gauge shows an alarm, in the displayed unit.
+#[derive(Deserialize)]
+pub struct AlarmConfig {
+    pub low: Option<f32>,
+    pub high: Option<f32>,
+    #[serde(default)]
+    pub filters: Vec<FilterConfig>, // Applied to the checked value, independently of the display
+}
+
+#[derive(Deserialize)]
+pub struct CalibrationPoint {
+    pub raw: f32,
+    pub value: f32,
+}
+
+/// Which extremes a gauge holds, until they are reset.
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+pub enum PeakHold {
+    Max,
+    Min,
+    Both,
+}
+
 #[derive(Deserialize)]
 pub enum GaugeType {
     Dial,
     TextGauge,
+    Lamp,
+    LabelGauge,
 }
 
 #[derive(Deserialize)]
@@ -45,6 +302,9 @@ pub enum GaugeDataType {
     I8,
     B8,
     B16,
+    F32,
+    U32,
+    I32,
 }
 
 #[cfg(feature = "colors")]
@@ -72,60 +332,143 @@ impl GaugeDataType {
             GaugeDataType::I8 => 1,
             GaugeDataType::B8 => 1,
             GaugeDataType::B16 => 2,
+            GaugeDataType::F32 => 4,
+            GaugeDataType::U32 => 4,
+            GaugeDataType::I32 => 4,
         }
     }
 
-    pub fn value(&self, data: &[u8], slot_start: u8) -> f32 {
-        let slot_end: usize = (slot_start + self.slot_size()).into();
-        let sliced_data = &data[slot_start.into()..slot_end];
+    pub fn signal(&self, slot_start: usize, byte_order: ByteOrder) -> Signal {
+        let value_type = match self {
+            GaugeDataType::F16 | GaugeDataType::F32 => ValueType::Float,
+            GaugeDataType::I8 | GaugeDataType::I16 | GaugeDataType::I32 => ValueType::Signed,
+            _ => ValueType::Unsigned,
+        };
 
-        match self {
-            &GaugeDataType::F16 => {
-                let d: &[u8; 2] = &sliced_data[slot_start.into()..slot_end]
-                    .try_into()
-                    .expect("Failure");
-                return f16::from_be_bytes(*d).into();
-            },
-            &GaugeDataType::U16 => {
-                let d: &[u8; 2] = &sliced_data[slot_start.into()..slot_end]
-                    .try_into()
-                    .expect("Failure");
-                return u16::from_be_bytes(*d).into();
-            },
-            &GaugeDataType::I16 => {
-                let d: &[u8; 2] = &sliced_data[slot_start.into()..slot_end]
-                    .try_into()
-                    .expect("Failure");
-                return i16::from_be_bytes(*d).into();
-            },
-            &GaugeDataType::U8 => {
-                let d: &[u8; 1] = &sliced_data[slot_start.into()..slot_end]
-                    .try_into()
-                    .expect("Failure");
-                return u8::from_be_bytes(*d).into();
-            },
-            &GaugeDataType::I8 => {
-                let d: &[u8; 1] = &sliced_data[slot_start.into()..slot_end]
-                    .try_into()
-                    .expect("Failure");
-                return i8::from_be_bytes(*d).into();
-            },
-            &GaugeDataType::B8 => {
-                let d: &[u8; 1] = &sliced_data[slot_start.into()..slot_end]
-                    .try_into()
-                    .expect("Failure");
-                return u8::from_be_bytes(*d).into();
-            },
-            &GaugeDataType::B16 => {
-                let d: &[u8; 2] = &sliced_data[slot_start.into()..slot_end]
-                    .try_into()
-                    .expect("Failure");
-                return u16::from_be_bytes(*d).into();
-            },
+        // Big endian signals start at the MSB of their first byte.
+        let start_bit = match byte_order {
+            ByteOrder::BigEndian => bit_position(slot_start, 7),
+            ByteOrder::LittleEndian => bit_position(slot_start, 0),
+        };
+
+        Signal {
+            start_bit,
+            length: u16::from(self.slot_size()) * 8,
+            byte_order,
+            value_type,
+            factor: 1.0,
+            offset: 0.0,
+        }
+    }
+
+    /// A single bit of a bitfield slot.
+    pub fn bit_signal(&self, slot_start: usize, bit: u8, byte_order: ByteOrder) -> Signal {
+        let slot_size = usize::from(self.slot_size());
+        let bit = usize::from(bit);
+        let byte = match byte_order {
+            ByteOrder::BigEndian => slot_start + slot_size - 1 - bit / 8,
+            ByteOrder::LittleEndian => slot_start + bit / 8,
+        };
+
+        Signal {
+            start_bit: bit_position(byte, bit % 8),
+            length: 1,
+            byte_order,
+            value_type: ValueType::Unsigned,
+            factor: 1.0,
+            offset: 0.0,
         }
     }
 }
 
+/// The DBC bit number of a bit in a byte. Positions past what it can hold are
+/// saturated, as they lie past the largest payload and decode as out of range.
+fn bit_position(byte: usize, bit: usize) -> u16 {
+    byte
+        .checked_mul(8)
+        .and_then(|position| position.checked_add(bit))
+        .and_then(|position| u16::try_from(position).ok())
+        .unwrap_or(u16::MAX)
+}
+
+impl Source {
+    pub fn frame_filter(&self) -> Option<FrameFilter> {
+        if let Some(pgn) = self.pgn {
+            return Some(j1939::filter(pgn, self.source_address));
+        }
+
+        let [first_id, last_id] = match self.frame_id_range {
+            Some(range) => range,
+            None => [self.frame_id?; 2],
+        };
+        let extended = self.extended.unwrap_or(last_id > 0x7FF);
+        let mask = self.frame_mask.unwrap_or(0x1FFF_FFFF);
+
+        Some(FrameFilter {
+            extended,
+            first_id: first_id & mask,
+            last_id: last_id & mask,
+            mask,
+        })
+    }
+
+    /// The layout of the gauge value, if it is not taken from a DBC file.
+    pub fn signal(&self, slot_size: u8) -> Option<Signal> {
+        let byte_order = self.byte_order.unwrap_or(ByteOrder::BigEndian);
+
+        match self.start_bit {
+            Some(start_bit) => Some(Signal {
+                start_bit,
+                length: self.bit_length?,
+                byte_order,
+                value_type: match self.signed {
+                    Some(true) => ValueType::Signed,
+                    _ => ValueType::Unsigned,
+                },
+                factor: 1.0,
+                offset: 0.0,
+            }),
+            None => {
+                let slot_id = self.slot_id?;
+                if slot_id == 0 {
+                    panic!("slot_id is 1-indexed, there is no slot 0");
+                }
+                let slot_start = usize::from(slot_id - 1) * usize::from(slot_size);
+                let data_type = self.data_type.as_ref()?;
+                match (data_type, self.bit) {
+                    (GaugeDataType::B8, Some(bit)) | (GaugeDataType::B16, Some(bit)) => {
+                        let bits = data_type.slot_size() * 8;
+                        if bit >= bits {
+                            panic!("bit {} is outside the {} bits of its slot, bits count from 0", bit, bits);
+                        }
+                        Some(data_type.bit_signal(slot_start, bit, byte_order))
+                    }
+                    _ => Some(data_type.signal(slot_start, byte_order)),
+                }
+            }
+        }
+    }
+
+    pub fn multiplexer(&self) -> Option<Multiplexer> {
+        let multiplexer = self.multiplexer.as_ref()?;
+
+        Some(Multiplexer {
+            signal: Signal {
+                start_bit: multiplexer.start_bit,
+                length: multiplexer.bit_length.unwrap_or(8),
+                byte_order: multiplexer
+                    .byte_order
+                    .or(self.byte_order)
+                    .unwrap_or(ByteOrder::BigEndian),
+                value_type: ValueType::Unsigned,
+                factor: 1.0,
+                offset: 0.0,
+            },
+            value: multiplexer.value,
+        })
+    }
+}
+
 #[derive(Deserialize)]
 pub struct StartPoint {
     pub x: i32,
@@ -137,3 +480,48 @@ pub struct AreaSize {
     pub width: u32,
     pub height: u32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signal::DecodeError;
+
+    fn source(toml: &str) -> Source {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn lays_out_slots() {
+        let signal = source("slot_id = 2\ndata_type = \"U16\"").signal(2).unwrap();
+        assert_eq!(signal.start_bit, 2 * 8 + 7);
+        assert_eq!(signal.raw(&[0, 0, 0x12, 0x34]), Ok(0x1234));
+    }
+
+    #[test]
+    fn slots_past_byte_255_are_not_wrapped() {
+        let signal = source("slot_id = 200\ndata_type = \"U8\"").signal(2).unwrap();
+        assert_eq!(signal.start_bit, 398 * 8 + 7);
+
+        let beyond = source("slot_id = 255\ndata_type = \"U32\"").signal(255).unwrap();
+        assert!(matches!(beyond.raw(&[0; 8]), Err(DecodeError::OutOfRange { .. })));
+    }
+
+    #[test]
+    fn lays_out_bits() {
+        let signal = source("slot_id = 1\ndata_type = \"B16\"\nbit = 9").signal(2).unwrap();
+        assert_eq!(signal.raw(&[0x02, 0x00]), Ok(1));
+        assert_eq!(signal.raw(&[0x00, 0x02]), Ok(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "outside the 8 bits")]
+    fn rejects_bits_outside_the_slot() {
+        source("slot_id = 1\ndata_type = \"B8\"\nbit = 8").signal(2);
+    }
+
+    #[test]
+    #[should_panic(expected = "1-indexed")]
+    fn rejects_slot_0() {
+        source("slot_id = 0\ndata_type = \"U8\"").signal(2);
+    }
+}
@@ -0,0 +1,313 @@
+use crate::signal::{ByteOrder, Multiplexer, Signal, ValueType};
+use std::fmt;
+use std::fs;
+
+/// Signal definitions read from one or more Vector DBC files.
+pub struct Dbc {
+    pub messages: Vec<Message>,
+}
+
+pub struct Message {
+    pub id: u32,
+    pub extended: bool,
+    pub name: String,
+    pub signals: Vec<DbcSignal>,
+}
+
+pub struct DbcSignal {
+    pub name: String,
+    pub signal: Signal,
+    pub min: f32,
+    pub max: f32,
+    pub unit: String,
+    pub multiplexer: Option<Multiplexer>,
+    pub values: ValueTable,
+}
+
+/// Labels of raw values, from VAL_.
+pub type ValueTable = Vec<(i64, String)>;
+
+enum MultiplexIndicator {
+    None,
+    Multiplexer,
+    Multiplexed(u64),
+}
+
+#[derive(Debug)]
+pub struct DbcError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for DbcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Dbc {
+    pub fn load(paths: &[String]) -> Dbc {
+        let mut dbc = Dbc { messages: Vec::new() };
+        for path in paths.iter() {
+            // DBC files are commonly saved as Windows-1252, which matches Latin-1 for
+            // the characters units use, e.g. 0xB0 for °. Files in UTF-8 are read as such.
+            let bytes = fs::read(path).unwrap();
+            let content = String::from_utf8(bytes)
+                .unwrap_or_else(|e| e.into_bytes().iter().map(|&byte| byte as char).collect());
+            let parsed = Dbc::parse(&content)
+                .unwrap_or_else(|e| panic!("Failed to parse {}: {}", path, e));
+            dbc.messages.extend(parsed.messages);
+        }
+        dbc
+    }
+
+    pub fn parse(content: &str) -> Result<Dbc, DbcError> {
+        let mut messages: Vec<Message> = Vec::new();
+        let mut indicators: Vec<Vec<MultiplexIndicator>> = Vec::new();
+        // Float signals are declared separately from the signals themselves.
+        let mut value_types: Vec<(u32, String, ValueType, u16)> = Vec::new();
+        let mut value_tables: Vec<(u32, String, ValueTable)> = Vec::new();
+
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            let error = |message: &str| DbcError {
+                line: index + 1,
+                message: message.to_string(),
+            };
+
+            if let Some(rest) = line.strip_prefix("BO_ ") {
+                messages.push(parse_message(rest).ok_or_else(|| error("malformed BO_"))?);
+                indicators.push(Vec::new());
+            } else if let Some(rest) = line.strip_prefix("SG_ ") {
+                let message = messages
+                    .last_mut()
+                    .ok_or_else(|| error("SG_ outside of a BO_"))?;
+                let (signal, indicator) = parse_signal(rest).ok_or_else(|| error("malformed SG_"))?;
+                message.signals.push(signal);
+                indicators.last_mut().unwrap().push(indicator);
+            } else if let Some(rest) = line.strip_prefix("SIG_VALTYPE_ ") {
+                let mut parts = rest
+                    .split(|c: char| c.is_whitespace() || c == ':' || c == ';')
+                    .filter(|p| !p.is_empty());
+                let id = parts.next().and_then(|p| p.parse::<u32>().ok());
+                let name = parts.next();
+                let kind = parts.next();
+                match (id, name, kind) {
+                    (Some(id), Some(name), Some("1")) => {
+                        value_types.push((id, name.to_string(), ValueType::Float, 32))
+                    }
+                    (Some(id), Some(name), Some("2")) => {
+                        value_types.push((id, name.to_string(), ValueType::Float, 64))
+                    }
+                    (Some(_), Some(_), Some(_)) => {}
+                    _ => return Err(error("malformed SIG_VALTYPE_")),
+                }
+            } else if let Some(rest) = line.strip_prefix("VAL_ ") {
+                // Environment variables have value descriptions too, named rather than by message ID.
+                let first = rest.split_whitespace().next().unwrap_or("");
+                if first.parse::<u32>().is_ok() {
+                    value_tables.push(parse_value_table(rest).ok_or_else(|| error("malformed VAL_"))?);
+                }
+            }
+        }
+
+        // The multiplexer signal may be declared after the signals it selects.
+        for (message, indicators) in messages.iter_mut().zip(indicators.iter()) {
+            let multiplexer = message
+                .signals
+                .iter()
+                .zip(indicators.iter())
+                .find(|(_, indicator)| matches!(indicator, MultiplexIndicator::Multiplexer))
+                .map(|(signal, _)| signal.signal.clone());
+
+            for (signal, indicator) in message.signals.iter_mut().zip(indicators.iter()) {
+                if let MultiplexIndicator::Multiplexed(value) = indicator {
+                    signal.multiplexer = multiplexer.clone().map(|signal| Multiplexer {
+                        signal,
+                        value: *value,
+                    });
+                }
+            }
+        }
+
+        for (raw_id, name, value_type, length) in value_types {
+            let signal = messages
+                .iter_mut()
+                .filter(|m| m.raw_id() == raw_id)
+                .flat_map(|m| m.signals.iter_mut())
+                .find(|s| s.name == name);
+            if let Some(signal) = signal {
+                signal.signal.value_type = value_type;
+                signal.signal.length = length;
+            }
+        }
+
+        for (raw_id, name, values) in value_tables {
+            let signal = messages
+                .iter_mut()
+                .filter(|m| m.raw_id() == raw_id)
+                .flat_map(|m| m.signals.iter_mut())
+                .find(|s| s.name == name);
+            if let Some(signal) = signal {
+                signal.values = values;
+            }
+        }
+
+        Ok(Dbc { messages })
+    }
+
+    /// Looks up a signal by its `Message.Signal` name.
+    pub fn find(&self, qualified_name: &str) -> Option<(&Message, &DbcSignal)> {
+        let (message_name, signal_name) = qualified_name.split_once('.')?;
+        let message = self.messages.iter().find(|m| m.name == message_name)?;
+        let signal = message.signals.iter().find(|s| s.name == signal_name)?;
+        Some((message, signal))
+    }
+}
+
+impl Message {
+    /// The ID as written in the DBC file, with bit 31 flagging extended frames.
+    fn raw_id(&self) -> u32 {
+        if self.extended {
+            self.id | 0x8000_0000
+        } else {
+            self.id
+        }
+    }
+}
+
+// BO_ 601 EngineData: 8 ECU
+fn parse_message(line: &str) -> Option<Message> {
+    let (head, tail) = line.split_once(':')?;
+    let mut head = head.split_whitespace();
+    let raw_id: u32 = head.next()?.parse().ok()?;
+    let name = head.next()?.to_string();
+    tail.split_whitespace().next()?.parse::<u8>().ok()?;
+
+    Some(Message {
+        id: raw_id & 0x1FFF_FFFF,
+        extended: raw_id & 0x8000_0000 != 0,
+        name,
+        signals: Vec::new(),
+    })
+}
+
+// SG_ Boost : 0|16@1- (0.001,-1) [-1|2] "bar" Vector__XXX
+// SG_ Temp m1 : 8|8@1+ (1,-40) [-40|215] "C" Vector__XXX
+fn parse_signal(line: &str) -> Option<(DbcSignal, MultiplexIndicator)> {
+    let (head, tail) = line.split_once(':')?;
+    let mut head = head.split_whitespace();
+    let name = head.next()?.to_string();
+    let indicator = match head.next() {
+        None => MultiplexIndicator::None,
+        Some("M") => MultiplexIndicator::Multiplexer,
+        // Extended multiplexing (m1M) is treated as plain multiplexing.
+        Some(indicator) => MultiplexIndicator::Multiplexed(
+            indicator
+                .strip_prefix('m')?
+                .trim_end_matches('M')
+                .parse()
+                .ok()?,
+        ),
+    };
+
+    let tail = tail.trim();
+    let (layout, tail) = tail.split_once(' ')?;
+    let (start_bit, layout) = layout.split_once('|')?;
+    let (length, layout) = layout.split_once('@')?;
+    let start_bit: u16 = start_bit.parse().ok()?;
+    let length: u16 = length.parse().ok()?;
+    if length == 0 || length > 64 {
+        return None;
+    }
+    let byte_order = match layout.get(0..1)? {
+        "0" => ByteOrder::BigEndian,
+        "1" => ByteOrder::LittleEndian,
+        _ => return None,
+    };
+    let value_type = match layout.get(1..2)? {
+        "+" => ValueType::Unsigned,
+        "-" => ValueType::Signed,
+        _ => return None,
+    };
+
+    let (scaling, tail) = between(tail, '(', ')')?;
+    let (factor, offset) = scaling.split_once(',')?;
+    let (range, tail) = between(tail, '[', ']')?;
+    let (min, max) = range.split_once('|')?;
+    let (unit, _) = between(tail, '"', '"')?;
+
+    let signal = DbcSignal {
+        name,
+        signal: Signal {
+            start_bit,
+            length,
+            byte_order,
+            value_type,
+            factor: factor.trim().parse().ok()?,
+            offset: offset.trim().parse().ok()?,
+        },
+        min: min.trim().parse().ok()?,
+        max: max.trim().parse().ok()?,
+        unit: unit.to_string(),
+        multiplexer: None,
+        values: Vec::new(),
+    };
+    Some((signal, indicator))
+}
+
+// VAL_ 601 Gear 0 "P" 1 "R" 2 "N" 3 "D" ;
+fn parse_value_table(line: &str) -> Option<(u32, String, ValueTable)> {
+    let mut head = line.splitn(3, char::is_whitespace);
+    let raw_id: u32 = head.next()?.parse().ok()?;
+    let name = head.next()?.to_string();
+    let mut rest = head.next()?.trim();
+
+    let mut values = Vec::new();
+    while let Some((value, tail)) = rest.split_once('"') {
+        let (label, tail) = tail.split_once('"')?;
+        values.push((value.trim().parse().ok()?, label.to_string()));
+        rest = tail;
+    }
+    if rest.trim() != ";" {
+        return None;
+    }
+    Some((raw_id, name, values))
+}
+
+/// Returns the text between `open` and the following `close`, and the remainder after it.
+fn between(text: &str, open: char, close: char) -> Option<(&str, &str)> {
+    let start = text.find(open)? + open.len_utf8();
+    let end = start + text[start..].find(close)?;
+    Some((&text[start..end], &text[end + close.len_utf8()..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DBC: &str = r#"
+BO_ 1543 Gearbox: 8 TCU
+ SG_ Gear : 7|8@0+ (1,0) [0|7] "" Vector__XXX
+
+VAL_ 1543 Gear 0 "N" 1 "1st" 7 "R" ;
+VAL_ EnvIgnition 0 "Off" 1 "On" ;
+"#;
+
+    #[test]
+    fn reads_value_tables_and_skips_environment_variables() {
+        let dbc = Dbc::parse(DBC).unwrap();
+        let (message, signal) = dbc.find("Gearbox.Gear").unwrap();
+        assert_eq!(message.id, 1543);
+        assert_eq!(
+            signal.values,
+            vec![(0, "N".to_string()), (1, "1st".to_string()), (7, "R".to_string())]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_value_tables() {
+        let dbc = format!("{}VAL_ 1543 Gear 0 \"N ;\n", DBC);
+        assert!(Dbc::parse(&dbc).is_err());
+    }
+}
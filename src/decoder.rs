@@ -0,0 +1,123 @@
+use crate::calibration::Calibration;
+use crate::can::{Frame, FrameFilter};
+use crate::config::Source;
+use crate::dbc::{Dbc, DbcSignal, Message};
+use crate::j1939;
+use crate::signal::{DecodeError, Multiplexer, Signal};
+use crate::uds;
+
+/// Decodes one value from the frames matching its filter.
+pub struct Decoder {
+    pub filter: FrameFilter,
+    pub signal: Signal,
+    pub multiplexer: Option<Multiplexer>,
+    pub calibration: Option<Calibration>,
+    pub raw: bool, // Also reads frames that a transport protocol takes, e.g. OBD-II replies
+    pub j1939: bool, // The top raw values report an error, or that the value isn't available
+}
+
+impl Decoder {
+    /// Builds the decoder for a gauge or channel called `name`. Also returns the DBC
+    /// signal it refers to, if any, for the defaults it provides.
+    pub fn new<'a>(
+        source: &Source,
+        name: &str,
+        slot_size: u8,
+        dbc: &'a Dbc,
+    ) -> (Decoder, Option<&'a DbcSignal>) {
+        let (mut decoder, dbc_signal) = match (&source.signal, source.spn) {
+            (Some(signal_name), _) => {
+                let (message, dbc_signal) = dbc
+                    .find(signal_name)
+                    .unwrap_or_else(|| panic!("Signal {} not found in the DBC files", signal_name));
+                (Decoder::from_dbc(message, dbc_signal), Some(dbc_signal))
+            }
+            (None, Some(spn)) => {
+                let spn = j1939::find(spn).unwrap_or_else(|| panic!("Unsupported J1939 SPN {}", spn));
+                (spn.decoder(source.source_address), None)
+            }
+            (None, None) => {
+                let signal = source
+                    .signal(slot_size)
+                    .unwrap_or_else(|| panic!("{} has no signal layout", name));
+                let decoder = match &source.uds {
+                    Some(uds) => uds::decoder(uds, signal),
+                    None => Decoder {
+                        filter: source
+                            .frame_filter()
+                            .unwrap_or_else(|| panic!("{} has no frame_id", name)),
+                        signal,
+                        multiplexer: None,
+                        calibration: None,
+                        raw: false,
+            j1939: false,
+                    },
+                };
+                (decoder, None)
+            }
+        };
+
+        if let Some(factor) = source.factor {
+            decoder.signal.factor = factor;
+        }
+        if let Some(offset) = source.offset {
+            decoder.signal.offset = offset;
+        }
+        if let Some(multiplexer) = source.multiplexer() {
+            decoder.multiplexer = Some(multiplexer);
+        }
+        decoder.calibration = source
+            .calibration
+            .as_ref()
+            .map(|points| Calibration::new(points.iter().map(|p| (p.raw, p.value)).collect()));
+
+        (decoder, dbc_signal)
+    }
+
+    pub fn from_dbc(message: &Message, dbc_signal: &DbcSignal) -> Decoder {
+        Decoder {
+            filter: FrameFilter::exact(message.id, message.extended),
+            signal: dbc_signal.signal.clone(),
+            multiplexer: dbc_signal.multiplexer.clone(),
+            calibration: None,
+            raw: false,
+            j1939: false,
+        }
+    }
+
+    /// The value a raw signal value decodes to.
+    pub fn scale(&self, raw: f32) -> f32 {
+        let value = raw * self.signal.factor + self.signal.offset;
+        match &self.calibration {
+            Some(calibration) => calibration.apply(value),
+            None => value,
+        }
+    }
+
+    /// Returns `None` if the frame doesn't carry the value.
+    pub fn decode(&self, frame: &Frame) -> Option<Result<f32, DecodeError>> {
+        if !self.filter.matches(frame) {
+            return None;
+        }
+        if let Some(multiplexer) = &self.multiplexer {
+            if !multiplexer.matches(&frame.data) {
+                return None;
+            }
+        }
+
+        if self.j1939 {
+            if let Err(error) = self
+                .signal
+                .raw(&frame.data)
+                .and_then(|raw| j1939::check(raw, self.signal.length))
+            {
+                return Some(Err(error));
+            }
+        }
+        let value = self.signal.value(&frame.data).map(|value| match &self.calibration {
+            Some(calibration) => calibration.apply(value),
+            None => value,
+        });
+        Some(value)
+    }
+}
@@ -0,0 +1,327 @@
+use std::fmt;
+
+/// An arithmetic expression over named channels, e.g. `Engine.MAP - Engine.Baro`.
+#[derive(Debug)]
+pub enum Expression {
+    Number(f32),
+    Variable(String),
+    Negate(Box<Expression>),
+    Binary(Operator, Box<Expression>, Box<Expression>),
+    Call(Function, Vec<Expression>),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Function {
+    Min,
+    Max,
+    Abs,
+}
+
+#[derive(Debug)]
+pub struct ExpressionError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at position {}: {}", self.position, self.message)
+    }
+}
+
+impl Expression {
+    pub fn parse(text: &str) -> Result<Expression, ExpressionError> {
+        let mut parser = Parser {
+            chars: text.chars().collect(),
+            position: 0,
+        };
+        let expression = parser.expression()?;
+        parser.skip_whitespace();
+        if parser.position < parser.chars.len() {
+            return Err(parser.error("unexpected input"));
+        }
+        Ok(expression)
+    }
+
+    /// The names of the channels the expression reads.
+    pub fn variables(&self) -> Vec<&str> {
+        let mut variables = Vec::new();
+        self.collect_variables(&mut variables);
+        variables
+    }
+
+    fn collect_variables<'a>(&'a self, variables: &mut Vec<&'a str>) {
+        match self {
+            Expression::Number(_) => {}
+            Expression::Variable(name) => variables.push(name),
+            Expression::Negate(operand) => operand.collect_variables(variables),
+            Expression::Binary(_, left, right) => {
+                left.collect_variables(variables);
+                right.collect_variables(variables);
+            }
+            Expression::Call(_, arguments) => {
+                for argument in arguments.iter() {
+                    argument.collect_variables(variables);
+                }
+            }
+        }
+    }
+
+    /// Evaluates the expression, or returns `None` while a variable has no value yet.
+    /// Errors of the variables are passed on.
+    pub fn evaluate<E, F>(&self, lookup: &F) -> Option<Result<f32, E>>
+    where
+        F: Fn(&str) -> Option<Result<f32, E>>,
+    {
+        let value = match self {
+            Expression::Number(value) => *value,
+            Expression::Variable(name) => match lookup(name)? {
+                Ok(value) => value,
+                Err(e) => return Some(Err(e)),
+            },
+            Expression::Negate(operand) => match operand.evaluate(lookup)? {
+                Ok(value) => -value,
+                Err(e) => return Some(Err(e)),
+            },
+            Expression::Binary(operator, left, right) => {
+                let left = match left.evaluate(lookup)? {
+                    Ok(value) => value,
+                    Err(e) => return Some(Err(e)),
+                };
+                let right = match right.evaluate(lookup)? {
+                    Ok(value) => value,
+                    Err(e) => return Some(Err(e)),
+                };
+                match operator {
+                    Operator::Add => left + right,
+                    Operator::Subtract => left - right,
+                    Operator::Multiply => left * right,
+                    Operator::Divide => left / right,
+                }
+            }
+            Expression::Call(function, arguments) => {
+                let mut values = Vec::with_capacity(arguments.len());
+                for argument in arguments.iter() {
+                    match argument.evaluate(lookup)? {
+                        Ok(value) => values.push(value),
+                        Err(e) => return Some(Err(e)),
+                    }
+                }
+                match function {
+                    Function::Min => values.iter().cloned().fold(f32::INFINITY, f32::min),
+                    Function::Max => values.iter().cloned().fold(f32::NEG_INFINITY, f32::max),
+                    Function::Abs => values[0].abs(),
+                }
+            }
+        };
+
+        Some(Ok(value))
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> ExpressionError {
+        ExpressionError {
+            position: self.position,
+            message: message.to_string(),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.position < self.chars.len() && self.chars[self.position].is_whitespace() {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.get(self.position).cloned()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ExpressionError> {
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected)))
+        }
+    }
+
+    // expression := term (('+' | '-') term)*
+    fn expression(&mut self) -> Result<Expression, ExpressionError> {
+        let mut left = self.term()?;
+        loop {
+            let operator = match self.peek() {
+                Some('+') => Operator::Add,
+                Some('-') => Operator::Subtract,
+                _ => return Ok(left),
+            };
+            self.position += 1;
+            let right = self.term()?;
+            left = Expression::Binary(operator, Box::new(left), Box::new(right));
+        }
+    }
+
+    // term := unary (('*' | '/') unary)*
+    fn term(&mut self) -> Result<Expression, ExpressionError> {
+        let mut left = self.unary()?;
+        loop {
+            let operator = match self.peek() {
+                Some('*') => Operator::Multiply,
+                Some('/') => Operator::Divide,
+                _ => return Ok(left),
+            };
+            self.position += 1;
+            let right = self.unary()?;
+            left = Expression::Binary(operator, Box::new(left), Box::new(right));
+        }
+    }
+
+    // unary := '-' unary | primary
+    fn unary(&mut self) -> Result<Expression, ExpressionError> {
+        if self.peek() == Some('-') {
+            self.position += 1;
+            return Ok(Expression::Negate(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    // primary := number | name | name '(' arguments ')' | '(' expression ')'
+    fn primary(&mut self) -> Result<Expression, ExpressionError> {
+        match self.peek() {
+            Some('(') => {
+                self.position += 1;
+                let expression = self.expression()?;
+                self.expect(')')?;
+                Ok(expression)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let start = self.position;
+                while self.position < self.chars.len()
+                    && (self.chars[self.position].is_ascii_digit() || self.chars[self.position] == '.')
+                {
+                    self.position += 1;
+                }
+                let text: String = self.chars[start..self.position].iter().collect();
+                text.parse()
+                    .map(Expression::Number)
+                    .map_err(|_| ExpressionError {
+                        position: start,
+                        message: format!("invalid number '{}'", text),
+                    })
+            }
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                let start = self.position;
+                while self.position < self.chars.len() && is_name_char(self.chars[self.position]) {
+                    self.position += 1;
+                }
+                let name: String = self.chars[start..self.position].iter().collect();
+
+                if self.peek() != Some('(') {
+                    return Ok(Expression::Variable(name));
+                }
+
+                let (function, arity) = match name.as_str() {
+                    "min" => (Function::Min, None),
+                    "max" => (Function::Max, None),
+                    "abs" => (Function::Abs, Some(1)),
+                    _ => {
+                        return Err(ExpressionError {
+                            position: start,
+                            message: format!("unknown function '{}'", name),
+                        })
+                    }
+                };
+                self.position += 1;
+                let mut arguments = vec![self.expression()?];
+                while self.peek() == Some(',') {
+                    self.position += 1;
+                    arguments.push(self.expression()?);
+                }
+                self.expect(')')?;
+                if arity.is_some_and(|arity| arity != arguments.len()) {
+                    return Err(ExpressionError {
+                        position: start,
+                        message: format!("wrong number of arguments to '{}'", name),
+                    });
+                }
+                Ok(Expression::Call(function, arguments))
+            }
+            _ => Err(self.error("expected a number, name or '('")),
+        }
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.' || c == ':'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(text: &str) -> Option<Result<f32, ()>> {
+        let lookup = |name: &str| match name {
+            "Engine.MAP" => Some(Ok(120.0)),
+            "Engine.Baro" => Some(Ok(100.0)),
+            "obd:0x0C" => Some(Err(())),
+            _ => None,
+        };
+        Expression::parse(text).unwrap().evaluate(&lookup)
+    }
+
+    #[test]
+    fn follows_precedence() {
+        assert_eq!(evaluate("1 + 2 * 3"), Some(Ok(7.0)));
+        assert_eq!(evaluate("(1 + 2) * 3"), Some(Ok(9.0)));
+        assert_eq!(evaluate("8 / 4 / 2"), Some(Ok(1.0)));
+        assert_eq!(evaluate("10 - 4 - 3"), Some(Ok(3.0)));
+        assert_eq!(evaluate("Engine.MAP - Engine.Baro * 0.5"), Some(Ok(70.0)));
+    }
+
+    #[test]
+    fn negates() {
+        assert_eq!(evaluate("-2 * 3"), Some(Ok(-6.0)));
+        assert_eq!(evaluate("4 - -2"), Some(Ok(6.0)));
+        assert_eq!(evaluate("--Engine.Baro"), Some(Ok(100.0)));
+    }
+
+    #[test]
+    fn calls_functions() {
+        assert_eq!(evaluate("min(3, 1, 2)"), Some(Ok(1.0)));
+        assert_eq!(evaluate("max(Engine.MAP, Engine.Baro)"), Some(Ok(120.0)));
+        assert_eq!(evaluate("abs(Engine.Baro - Engine.MAP)"), Some(Ok(20.0)));
+    }
+
+    #[test]
+    fn waits_for_unknown_names_and_passes_errors_on() {
+        assert_eq!(evaluate("Engine.Oil + 1"), None);
+        assert_eq!(evaluate("obd:0x0C * 2"), Some(Err(())));
+        let expression = Expression::parse("max(a, b.c) - a").unwrap();
+        assert_eq!(expression.variables(), vec!["a", "b.c", "a"]);
+    }
+
+    #[test]
+    fn reports_parse_errors() {
+        let error = |text: &str| Expression::parse(text).unwrap_err();
+        assert_eq!(error("1 +").position, 3);
+        assert_eq!(error("(1 + 2").message, "expected ')'");
+        assert_eq!(error("1 2").message, "unexpected input");
+        assert_eq!(error("1.2.3").message, "invalid number '1.2.3'");
+        assert_eq!(error("sqrt(4)").message, "unknown function 'sqrt'");
+        assert_eq!(error("abs(1, 2)").message, "wrong number of arguments to 'abs'");
+    }
+}
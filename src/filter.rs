@@ -0,0 +1,161 @@
+use crate::config::FilterConfig;
+use std::collections::VecDeque;
+use std::time::Instant;
+
+/// Smooths a stream of values, one value at a time.
+enum Filter {
+    Ema { alpha: f32, value: Option<f32> },
+    MovingAverage { samples: usize, values: VecDeque<f32> },
+    Median { samples: usize, values: VecDeque<f32> },
+    SlewRate { rate: f32, last: Option<(f32, Instant)> },
+}
+
+impl Filter {
+    fn new(config: &FilterConfig) -> Filter {
+        match *config {
+            FilterConfig::Ema { alpha } => Filter::Ema {
+                alpha: alpha.clamp(0.0, 1.0),
+                value: None,
+            },
+            FilterConfig::MovingAverage { samples } => Filter::MovingAverage {
+                samples: samples.max(1),
+                values: VecDeque::new(),
+            },
+            FilterConfig::Median { samples } => Filter::Median {
+                samples: samples.max(1),
+                values: VecDeque::new(),
+            },
+            FilterConfig::SlewRate { rate } => Filter::SlewRate {
+                rate: rate.abs(),
+                last: None,
+            },
+        }
+    }
+
+    fn apply(&mut self, value: f32, now: Instant) -> f32 {
+        // NaN, e.g. from a float signal or a division by zero, would stick in the state.
+        if !value.is_finite() {
+            return value;
+        }
+        match self {
+            Filter::Ema { alpha, value: smoothed } => {
+                let next = match *smoothed {
+                    Some(smoothed) => smoothed + *alpha * (value - smoothed),
+                    None => value,
+                };
+                *smoothed = Some(next);
+                next
+            }
+            Filter::MovingAverage { samples, values } => {
+                push_sample(values, *samples, value);
+                values.iter().sum::<f32>() / values.len() as f32
+            }
+            Filter::Median { samples, values } => {
+                push_sample(values, *samples, value);
+                let mut sorted: Vec<f32> = values.iter().cloned().collect();
+                sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+                let middle = sorted.len() / 2;
+                if sorted.len().is_multiple_of(2) {
+                    (sorted[middle - 1] + sorted[middle]) / 2.0
+                } else {
+                    sorted[middle]
+                }
+            }
+            Filter::SlewRate { rate, last } => {
+                // Moves towards the value by at most `rate` per second.
+                let next = match *last {
+                    Some((previous, at)) => {
+                        let step = *rate * now.duration_since(at).as_secs_f32();
+                        value.clamp(previous - step, previous + step)
+                    }
+                    None => value,
+                };
+                *last = Some((next, now));
+                next
+            }
+        }
+    }
+}
+
+fn push_sample(values: &mut VecDeque<f32>, samples: usize, value: f32) {
+    if values.len() == samples {
+        values.pop_front();
+    }
+    values.push_back(value);
+}
+
+/// Filters applied one after the other, in the order they are configured.
+pub struct FilterChain {
+    filters: Vec<Filter>,
+}
+
+impl FilterChain {
+    pub fn new(configs: &[FilterConfig]) -> FilterChain {
+        FilterChain {
+            filters: configs.iter().map(Filter::new).collect(),
+        }
+    }
+
+    pub fn apply(&mut self, value: f32, now: Instant) -> f32 {
+        self.filters
+            .iter_mut()
+            .fold(value, |value, filter| filter.apply(value, now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn passes_non_finite_values_through() {
+        let mut chain = FilterChain::new(&[
+            FilterConfig::Ema { alpha: 0.5 },
+            FilterConfig::SlewRate { rate: 10.0 },
+        ]);
+        let now = Instant::now();
+        assert_eq!(chain.apply(4.0, now), 4.0);
+        assert!(chain.apply(f32::NAN, now + Duration::from_millis(100)).is_nan());
+        assert_eq!(chain.apply(f32::INFINITY, now + Duration::from_millis(200)), f32::INFINITY);
+        // The filters carry on from the last finite value.
+        assert_eq!(chain.apply(6.0, now + Duration::from_millis(300)), 5.0);
+    }
+
+    fn outputs(config: FilterConfig, values: &[f32]) -> Vec<f32> {
+        let mut filter = Filter::new(&config);
+        let now = Instant::now();
+        values.iter().map(|value| filter.apply(*value, now)).collect()
+    }
+
+    #[test]
+    fn ema_moves_part_of_the_way() {
+        let outputs = outputs(FilterConfig::Ema { alpha: 0.25 }, &[8.0, 0.0, 0.0, 16.0]);
+        assert_eq!(outputs, vec![8.0, 6.0, 4.5, 7.375]);
+    }
+
+    #[test]
+    fn moving_average_covers_the_last_samples() {
+        let outputs = outputs(FilterConfig::MovingAverage { samples: 3 }, &[3.0, 6.0, 9.0, 12.0, 0.0]);
+        assert_eq!(outputs, vec![3.0, 4.5, 6.0, 9.0, 7.0]);
+    }
+
+    #[test]
+    fn median_drops_outliers() {
+        let odd = outputs(FilterConfig::Median { samples: 3 }, &[5.0, 100.0, 6.0, 7.0, -50.0]);
+        assert_eq!(odd, vec![5.0, 52.5, 6.0, 7.0, 6.0]);
+        let even = outputs(FilterConfig::Median { samples: 4 }, &[1.0, 9.0, 3.0, 5.0, 2.0]);
+        assert_eq!(even, vec![1.0, 5.0, 3.0, 4.0, 4.0]);
+    }
+
+    #[test]
+    fn slew_rate_limits_change_per_second() {
+        let mut filter = Filter::new(&FilterConfig::SlewRate { rate: 20.0 });
+        let now = Instant::now();
+        assert_eq!(filter.apply(0.0, now), 0.0);
+        assert_eq!(filter.apply(100.0, now + Duration::from_millis(500)), 10.0);
+        assert_eq!(filter.apply(100.0, now + Duration::from_millis(1500)), 30.0);
+        assert_eq!(filter.apply(25.0, now + Duration::from_millis(2000)), 25.0);
+        assert_eq!(filter.apply(-100.0, now + Duration::from_millis(2250)), 20.0);
+    }
+}
@@ -1,4 +1,5 @@
-use super::{Digits, DrawableWrapper, SetValue};
+use super::{Digits, DrawableWrapper, Peaks, SetValue, Status};
+use crate::config::PeakHold;
 use crate::Config;
 use embedded_graphics::{
     draw_target::DrawTarget,
@@ -24,10 +25,15 @@ pub struct Dial<'a> {
     pub min_value: f32,
     pub max_value: f32,
     pub current_value: f32,
+    pub status: Status,
+    pub alarm: bool,
+    pub peak_hold: Option<PeakHold>,
+    pub peaks: Peaks,
     pub digits: Digits,
     pub bounding: Rectangle,
 
     arc_stroke: PrimitiveStyle<Colour>,
+    stale_stroke: PrimitiveStyle<Colour>,
     outline: PrimitiveStyle<Colour>,
     character_style: MonoTextStyle<'a, Colour>,
     text_style: TextStyle,
@@ -51,12 +57,20 @@ impl Dial<'_> {
         let primary = Rgb888::new(config.colors.primary.r, config.colors.primary.g, config.colors.primary.b);
         #[cfg(not(feature = "colors"))]
         let primary = BinaryColor::On;
+        #[cfg(feature = "colors")]
+        let dimmed = Rgb888::new(primary.r() / 2, primary.g() / 2, primary.b() / 2);
+        #[cfg(not(feature = "colors"))]
+        let dimmed = BinaryColor::On;
 
         let mut ret = Dial {
             title,
             min_value,
             max_value,
             current_value,
+            status: Status::Valid,
+            alarm: false,
+            peak_hold: None,
+            peaks: Peaks::default(),
             digits,
             bounding,
 
@@ -65,6 +79,12 @@ impl Dial<'_> {
                 .stroke_width(5)
                 .stroke_alignment(StrokeAlignment::Inside)
                 .build(),
+            // The last value stays visible as a thin, dimmed arc while stale.
+            stale_stroke: PrimitiveStyleBuilder::new()
+                .stroke_color(dimmed)
+                .stroke_width(1)
+                .stroke_alignment(StrokeAlignment::Inside)
+                .build(),
             outline: PrimitiveStyleBuilder::new()
                 .stroke_color(primary)
                 .stroke_width(2)
@@ -123,13 +143,20 @@ impl Dial<'_> {
     }
 
     fn create_indicator_line(&mut self, value: f32) {
+        let line = self.indicator_line(value, self.inner_radius);
+        self.drawables
+            .push(DrawableWrapper::Line(line.into_styled(self.outline)));
+    }
+
+    /// A radial line at `value`, from `inner_radius` out to the outer edge of the arc.
+    fn indicator_line(&self, value: f32, inner_radius: u8) -> Line {
         let percentage = ((value - self.min_value) / (self.max_value - self.min_value)) * 100.0;
         let sweep = 360.0 - ((100.0 - percentage) * 270.0 / 100.0);
 
         let center = self.bounding.center();
         let inner_point = Point::new(
-            center.x + (self.inner_radius as f32 * sweep.to_radians().cos()).round() as i32,
-            center.y + (self.inner_radius as f32 * sweep.to_radians().sin()).round() as i32,
+            center.x + (inner_radius as f32 * sweep.to_radians().cos()).round() as i32,
+            center.y + (inner_radius as f32 * sweep.to_radians().sin()).round() as i32,
         );
 
         let outer_point = Point::new(
@@ -137,9 +164,7 @@ impl Dial<'_> {
             center.y + ((self.outer_radius - 1) as f32 * sweep.to_radians().sin()).round() as i32,
         );
 
-        self.drawables.push(DrawableWrapper::Line(
-            Line::new(inner_point, outer_point).into_styled(self.outline),
-        ));
+        Line::new(inner_point, outer_point)
     }
 }
 
@@ -163,17 +188,40 @@ impl Drawable for Dial<'_> {
             270.0.deg(),
             -sweep.deg(),
         );
-        arc.into_styled(self.arc_stroke).draw(target)?;
+        match self.status {
+            Status::Valid => arc.into_styled(self.arc_stroke).draw(target)?,
+            Status::Stale => arc.into_styled(self.stale_stroke).draw(target)?,
+            Status::Invalid | Status::Fault => {}
+        }
 
         // Draw centered text.
-        let text = match self.digits {
-            Digits::None => format!("{:.0}", self.current_value),
-            Digits::Single => format!("{:.1}", self.current_value),
-            Digits::Two => format!("{:.2}", self.current_value),
+        let text = match (self.status, &self.digits) {
+            (Status::Valid, Digits::None) => format!("{:.0}", self.current_value),
+            (Status::Valid, Digits::Single) => format!("{:.1}", self.current_value),
+            (Status::Valid, Digits::Two) => format!("{:.2}", self.current_value),
+            (status, _) => status.label().to_string(),
         };
         Text::with_text_style(&text, arc.center(), self.character_style, self.text_style)
             .draw(target)?;
 
+        if self.alarm {
+            self.bounding.into_styled(self.outline).draw(target)?;
+        }
+
+        // Held peaks reach further in than the fixed indicators.
+        let held = match self.peak_hold {
+            Some(PeakHold::Max) => [None, self.peaks.max],
+            Some(PeakHold::Min) => [self.peaks.min, None],
+            Some(PeakHold::Both) => [self.peaks.min, self.peaks.max],
+            None => [None, None],
+        };
+        for peak in held.iter().flatten() {
+            let value = peak.clamp(self.min_value, self.max_value);
+            self.indicator_line(value, self.inner_radius - 6)
+                .into_styled(self.outline)
+                .draw(target)?;
+        }
+
         let drawable_iter = self.drawables.iter();
         for drawable in drawable_iter {
             drawable.draw(target)?;
@@ -186,5 +234,19 @@ impl Drawable for Dial<'_> {
 impl SetValue for Dial<'_> {
     fn set_value(&mut self, value: f32) {
         self.current_value = value;
+        self.status = Status::Valid;
+        self.peaks.update(value);
+    }
+
+    fn set_status(&mut self, status: Status) {
+        self.status = status;
+    }
+
+    fn set_alarm(&mut self, alarm: bool) {
+        self.alarm = alarm;
+    }
+
+    fn reset_peaks(&mut self) {
+        self.peaks.reset();
     }
 }
@@ -0,0 +1,148 @@
+use super::{DrawableWrapper, SetValue, Status};
+use crate::Config;
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    mono_font::{ascii::FONT_6X9, MonoTextStyle},
+    prelude::{Point, Primitive},
+    primitives::{PrimitiveStyle, Rectangle},
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
+    Drawable,
+};
+
+#[cfg(feature = "colors")]
+use embedded_graphics::pixelcolor::Rgb888;
+#[cfg(not(feature = "colors"))]
+use embedded_graphics::pixelcolor::BinaryColor;
+
+#[cfg(feature = "colors")]
+type Colour = Rgb888;
+#[cfg(not(feature = "colors"))]
+type Colour = BinaryColor;
+
+/// Shows the label of an enumerated value, e.g. the gear or the selected map.
+pub struct LabelGauge<'a> {
+    pub value: f32,
+    pub status: Status,
+    pub alarm: bool,
+    pub labels: Vec<(i64, String)>,
+    pub fallback: Option<&'a str>, // Shown for values without a label, instead of the number
+
+    bounding_box: Rectangle,
+    character_style: MonoTextStyle<'a, Colour>,
+    alarm_style: PrimitiveStyle<Colour>,
+    drawables: Vec<DrawableWrapper<'a>>,
+}
+
+impl LabelGauge<'_> {
+    pub fn new<'a>(
+        title: &'a str,
+        labels: Vec<(i64, String)>,
+        fallback: Option<&'a str>,
+        bounding_box: Rectangle,
+        config: &Config,
+    ) -> LabelGauge<'a> {
+        #[cfg(feature = "colors")]
+        let primary = Rgb888::new(config.colors.primary.r, config.colors.primary.g, config.colors.primary.b);
+        #[cfg(not(feature = "colors"))]
+        let primary = BinaryColor::On;
+
+        let text_style = TextStyleBuilder::new()
+            .baseline(Baseline::Middle)
+            .alignment(Alignment::Left)
+            .build();
+        let character_style = MonoTextStyle::new(&FONT_6X9, primary);
+        let center = bounding_box.center();
+        let drawables: Vec<DrawableWrapper<'a>> = vec![DrawableWrapper::Text(Text::with_text_style(
+            title,
+            Point::new(bounding_box.top_left.x + 2, center.y),
+            character_style,
+            text_style,
+        ))];
+
+        LabelGauge {
+            value: 0.0,
+            status: Status::Valid,
+            alarm: false,
+            labels,
+            fallback,
+
+            bounding_box,
+            character_style,
+            alarm_style: PrimitiveStyle::with_stroke(primary, 1),
+            drawables,
+        }
+    }
+}
+
+impl Drawable for LabelGauge<'_> {
+    type Color = Colour;
+
+    type Output = ();
+
+    fn draw<D>(&self, target: &mut D) -> Result<Self::Output, <D as DrawTarget>::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        let text_style = TextStyleBuilder::new()
+            .baseline(Baseline::Middle)
+            .alignment(Alignment::Right)
+            .build();
+        let value = self.value.round() as i64;
+        let label = match self.status {
+            // A NaN would round to 0, and show its label.
+            Status::Valid if !self.value.is_finite() => self
+                .fallback
+                .unwrap_or_else(|| Status::Invalid.label())
+                .to_string(),
+            Status::Valid => match self.labels.iter().find(|(v, _)| *v == value) {
+                Some((_, label)) => label.clone(),
+                None => self
+                    .fallback
+                    .map(|fallback| fallback.to_string())
+                    .unwrap_or_else(|| value.to_string()),
+            },
+            status => status.label().to_string(),
+        };
+        Text::with_text_style(
+            &label,
+            Point::new(
+                self.bounding_box
+                    .anchor_point(embedded_graphics::geometry::AnchorPoint::TopRight)
+                    .x
+                    - 2,
+                self.bounding_box.center().y,
+            ),
+            self.character_style,
+            text_style,
+        )
+        .draw(target)?;
+
+        if self.alarm {
+            self.bounding_box.into_styled(self.alarm_style).draw(target)?;
+        }
+
+        let drawable_iter = self.drawables.iter();
+        for drawable in drawable_iter {
+            drawable.draw(target)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SetValue for LabelGauge<'_> {
+    fn set_value(&mut self, value: f32) {
+        self.value = value;
+        self.status = Status::Valid;
+    }
+
+    fn set_status(&mut self, status: Status) {
+        self.status = status;
+    }
+
+    fn set_alarm(&mut self, alarm: bool) {
+        self.alarm = alarm;
+    }
+
+    fn reset_peaks(&mut self) {}
+}
@@ -0,0 +1,131 @@
+use super::{DrawableWrapper, SetValue, Status};
+use crate::Config;
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    mono_font::{ascii::FONT_6X9, MonoTextStyle},
+    prelude::{Dimensions, Point, Primitive},
+    primitives::{Circle, Line, PrimitiveStyle, PrimitiveStyleBuilder, Rectangle, StrokeAlignment},
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
+    Drawable,
+};
+
+#[cfg(feature = "colors")]
+use embedded_graphics::pixelcolor::Rgb888;
+#[cfg(not(feature = "colors"))]
+use embedded_graphics::pixelcolor::BinaryColor;
+
+#[cfg(feature = "colors")]
+type Colour = Rgb888;
+#[cfg(not(feature = "colors"))]
+type Colour = BinaryColor;
+
+/// A warning lamp driven by a single bit, e.g. check engine or limp mode.
+pub struct Lamp<'a> {
+    pub active: bool,
+    pub active_low: bool,
+    pub status: Status,
+    pub alarm: bool, // Lights the lamp regardless of its value
+
+    lamp: Circle,
+    on_style: PrimitiveStyle<Colour>,
+    off_style: PrimitiveStyle<Colour>,
+    drawables: Vec<DrawableWrapper<'a>>,
+}
+
+impl Lamp<'_> {
+    pub fn new<'a>(
+        label: &'a str,
+        active_low: bool,
+        bounding_box: Rectangle,
+        config: &Config,
+    ) -> Lamp<'a> {
+        #[cfg(feature = "colors")]
+        let primary = Rgb888::new(config.colors.primary.r, config.colors.primary.g, config.colors.primary.b);
+        #[cfg(not(feature = "colors"))]
+        let primary = BinaryColor::On;
+
+        let text_style = TextStyleBuilder::new()
+            .baseline(Baseline::Middle)
+            .alignment(Alignment::Left)
+            .build();
+        let character_style = MonoTextStyle::new(&FONT_6X9, primary);
+        let diameter = bounding_box.size.height.saturating_sub(2);
+        let center = bounding_box.center();
+        let drawables: Vec<DrawableWrapper<'a>> = vec![DrawableWrapper::Text(Text::with_text_style(
+            label,
+            Point::new(bounding_box.top_left.x + diameter as i32 + 6, center.y),
+            character_style,
+            text_style,
+        ))];
+
+        Lamp {
+            active: false,
+            active_low,
+            status: Status::Valid,
+            alarm: false,
+
+            lamp: Circle::new(
+                Point::new(bounding_box.top_left.x + 2, bounding_box.top_left.y + 1),
+                diameter,
+            ),
+            on_style: PrimitiveStyle::with_fill(primary),
+            off_style: PrimitiveStyleBuilder::new()
+                .stroke_color(primary)
+                .stroke_width(1)
+                .stroke_alignment(StrokeAlignment::Inside)
+                .build(),
+            drawables,
+        }
+    }
+}
+
+impl Drawable for Lamp<'_> {
+    type Color = Colour;
+
+    type Output = ();
+
+    fn draw<D>(&self, target: &mut D) -> Result<Self::Output, <D as DrawTarget>::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        let style = if (self.active || self.alarm) && self.status == Status::Valid {
+            self.on_style
+        } else {
+            self.off_style
+        };
+        self.lamp.into_styled(style).draw(target)?;
+
+        // Cross out the lamp while its state is unknown.
+        if self.status != Status::Valid {
+            let bounding_box = self.lamp.bounding_box();
+            let bottom_right = bounding_box.bottom_right().unwrap_or(bounding_box.top_left);
+            Line::new(bounding_box.top_left, bottom_right)
+                .into_styled(self.off_style)
+                .draw(target)?;
+        }
+
+        let drawable_iter = self.drawables.iter();
+        for drawable in drawable_iter {
+            drawable.draw(target)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SetValue for Lamp<'_> {
+    fn set_value(&mut self, value: f32) {
+        self.active = (value != 0.0) != self.active_low;
+        self.status = Status::Valid;
+    }
+
+    fn set_status(&mut self, status: Status) {
+        self.status = status;
+    }
+
+    fn set_alarm(&mut self, alarm: bool) {
+        self.alarm = alarm;
+    }
+
+    fn reset_peaks(&mut self) {}
+}
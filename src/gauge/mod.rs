@@ -1,4 +1,6 @@
 pub mod dial;
+pub mod labelgauge;
+pub mod lamp;
 pub mod textgauge;
 
 use embedded_graphics::{
@@ -25,13 +27,70 @@ pub enum Digits {
     Two,
 }
 
+impl Digits {
+    fn format(&self, value: f32) -> String {
+        match self {
+            Digits::None => format!("{:.0}", value),
+            Digits::Single => format!("{:.1}", value),
+            Digits::Two => format!("{:.2}", value),
+        }
+    }
+}
+
+/// The lowest and highest values since the last reset.
+#[derive(Default)]
+pub struct Peaks {
+    pub min: Option<f32>,
+    pub max: Option<f32>,
+}
+
+impl Peaks {
+    fn update(&mut self, value: f32) {
+        // A NaN would stick as the first peak, and infinities would never be beaten.
+        if !value.is_finite() {
+            return;
+        }
+        self.min = Some(self.min.map_or(value, |min| min.min(value)));
+        self.max = Some(self.max.map_or(value, |max| max.max(value)));
+    }
+
+    fn reset(&mut self) {
+        *self = Peaks::default();
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Status {
+    Valid,
+    Invalid, // The last frame couldn't be decoded
+    Fault,   // The last frame failed its counter or checksum check
+    Stale,   // No data arrived within the gauge's timeout
+}
+
+impl Status {
+    /// Text shown in place of the value, while there's no valid value.
+    fn label(&self) -> &'static str {
+        match self {
+            Status::Valid => "",
+            Status::Invalid => "ERR",
+            Status::Fault => "FLT",
+            Status::Stale => "--",
+        }
+    }
+}
+
 pub trait SetValue {
     fn set_value(&mut self, value: f32);
+    fn set_status(&mut self, status: Status);
+    fn set_alarm(&mut self, alarm: bool);
+    fn reset_peaks(&mut self);
 }
 
 pub enum Gauge<'a> {
     Dial(dial::Dial<'a>),
     TextGauge(textgauge::TextGauge<'a>),
+    Lamp(lamp::Lamp<'a>),
+    LabelGauge(labelgauge::LabelGauge<'a>),
 }
 
 impl SetValue for Gauge<'_> {
@@ -39,6 +98,35 @@ impl SetValue for Gauge<'_> {
         match self {
             Gauge::Dial(dial) => dial.set_value(value),
             Gauge::TextGauge(textgauge) => textgauge.set_value(value),
+            Gauge::Lamp(lamp) => lamp.set_value(value),
+            Gauge::LabelGauge(labelgauge) => labelgauge.set_value(value),
+        }
+    }
+
+    fn set_status(&mut self, status: Status) {
+        match self {
+            Gauge::Dial(dial) => dial.set_status(status),
+            Gauge::TextGauge(textgauge) => textgauge.set_status(status),
+            Gauge::Lamp(lamp) => lamp.set_status(status),
+            Gauge::LabelGauge(labelgauge) => labelgauge.set_status(status),
+        }
+    }
+
+    fn set_alarm(&mut self, alarm: bool) {
+        match self {
+            Gauge::Dial(dial) => dial.set_alarm(alarm),
+            Gauge::TextGauge(textgauge) => textgauge.set_alarm(alarm),
+            Gauge::Lamp(lamp) => lamp.set_alarm(alarm),
+            Gauge::LabelGauge(labelgauge) => labelgauge.set_alarm(alarm),
+        }
+    }
+
+    fn reset_peaks(&mut self) {
+        match self {
+            Gauge::Dial(dial) => dial.reset_peaks(),
+            Gauge::TextGauge(textgauge) => textgauge.reset_peaks(),
+            Gauge::Lamp(lamp) => lamp.reset_peaks(),
+            Gauge::LabelGauge(labelgauge) => labelgauge.reset_peaks(),
         }
     }
 }
@@ -55,6 +143,8 @@ impl Drawable for Gauge<'_> {
         match self {
             Gauge::Dial(dial) => Ok(dial.draw(target)?),
             Gauge::TextGauge(textgauge) => Ok(textgauge.draw(target)?),
+            Gauge::Lamp(lamp) => Ok(lamp.draw(target)?),
+            Gauge::LabelGauge(labelgauge) => Ok(labelgauge.draw(target)?),
         }
     }
 }
@@ -84,3 +174,35 @@ impl Drawable for DrawableWrapper<'_> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peaks_follow_the_extremes_until_reset() {
+        let mut peaks = Peaks::default();
+        assert_eq!((peaks.min, peaks.max), (None, None));
+        for value in [3.0, -1.5, 7.25, 2.0].iter() {
+            peaks.update(*value);
+        }
+        assert_eq!((peaks.min, peaks.max), (Some(-1.5), Some(7.25)));
+
+        peaks.reset();
+        assert_eq!((peaks.min, peaks.max), (None, None));
+        peaks.update(4.0);
+        assert_eq!((peaks.min, peaks.max), (Some(4.0), Some(4.0)));
+    }
+
+    #[test]
+    fn peaks_skip_non_finite_values() {
+        let mut peaks = Peaks::default();
+        peaks.update(f32::NAN);
+        assert_eq!((peaks.min, peaks.max), (None, None));
+        peaks.update(1.0);
+        peaks.update(f32::INFINITY);
+        peaks.update(f32::NEG_INFINITY);
+        peaks.update(f32::NAN);
+        assert_eq!((peaks.min, peaks.max), (Some(1.0), Some(1.0)));
+    }
+}
@@ -1,10 +1,11 @@
-use super::{Digits, DrawableWrapper, SetValue};
+use super::{Digits, DrawableWrapper, Peaks, SetValue, Status};
+use crate::config::PeakHold;
 use crate::Config;
 use embedded_graphics::{
     draw_target::DrawTarget,
     mono_font::{ascii::FONT_6X9, MonoTextStyle},
-    prelude::Point,
-    primitives::Rectangle,
+    prelude::{Point, Primitive},
+    primitives::{PrimitiveStyle, Rectangle},
     text::{Alignment, Baseline, Text, TextStyleBuilder},
     Drawable,
 };
@@ -23,10 +24,15 @@ pub struct TextGauge<'a> {
     pub title: &'a str,
     pub unit: &'a str,
     pub value: f32,
+    pub status: Status,
+    pub alarm: bool,
+    pub peak_hold: Option<PeakHold>, // Shown as a small readout, e.g. "max 1.25"
+    pub peaks: Peaks,
     pub digits: Digits,
 
     bounding_box: Rectangle,
     character_style: MonoTextStyle<'a, Colour>,
+    alarm_style: PrimitiveStyle<Colour>,
     drawables: Vec<DrawableWrapper<'a>>,
 }
 
@@ -63,10 +69,15 @@ impl TextGauge<'_> {
             title,
             unit,
             value,
+            status: Status::Valid,
+            alarm: false,
+            peak_hold: None,
+            peaks: Peaks::default(),
             digits,
 
             bounding_box,
             character_style,
+            alarm_style: PrimitiveStyle::with_stroke(primary, 1),
             drawables,
         }
     }
@@ -85,10 +96,11 @@ impl Drawable for TextGauge<'_> {
             .baseline(Baseline::Middle)
             .alignment(Alignment::Right)
             .build();
-        let value = match self.digits {
-            Digits::None => format!("{:.0}", self.value),
-            Digits::Single => format!("{:.1}", self.value),
-            Digits::Two => format!("{:.2}", self.value),
+        let value = match (self.status, &self.digits) {
+            (Status::Valid, Digits::None) => format!("{:.0}", self.value),
+            (Status::Valid, Digits::Single) => format!("{:.1}", self.value),
+            (Status::Valid, Digits::Two) => format!("{:.2}", self.value),
+            (status, _) => status.label().to_string(),
         };
         Text::with_text_style(
             &format!("{} {}", value, self.unit),
@@ -104,6 +116,33 @@ impl Drawable for TextGauge<'_> {
         )
         .draw(target)?;
 
+        if self.alarm {
+            self.bounding_box.into_styled(self.alarm_style).draw(target)?;
+        }
+
+        let format_peak = |label: &str, peak: Option<f32>| match peak {
+            Some(peak) => format!("{} {}", label, self.digits.format(peak)),
+            None => format!("{} -", label),
+        };
+        let peaks = match self.peak_hold {
+            Some(PeakHold::Max) => Some(format_peak("max", self.peaks.max)),
+            Some(PeakHold::Min) => Some(format_peak("min", self.peaks.min)),
+            Some(PeakHold::Both) => Some(format!(
+                "{} {}",
+                format_peak("min", self.peaks.min),
+                format_peak("max", self.peaks.max)
+            )),
+            None => None,
+        };
+        if let Some(peaks) = peaks {
+            let peak_style = TextStyleBuilder::new()
+                .baseline(Baseline::Middle)
+                .alignment(Alignment::Center)
+                .build();
+            Text::with_text_style(&peaks, self.bounding_box.center(), self.character_style, peak_style)
+                .draw(target)?;
+        }
+
         let drawable_iter = self.drawables.iter();
         for drawable in drawable_iter {
             drawable.draw(target)?;
@@ -116,5 +155,19 @@ impl Drawable for TextGauge<'_> {
 impl SetValue for TextGauge<'_> {
     fn set_value(&mut self, value: f32) {
         self.value = value;
+        self.status = Status::Valid;
+        self.peaks.update(value);
+    }
+
+    fn set_status(&mut self, status: Status) {
+        self.status = status;
+    }
+
+    fn set_alarm(&mut self, alarm: bool) {
+        self.alarm = alarm;
+    }
+
+    fn reset_peaks(&mut self) {
+        self.peaks.reset();
     }
 }
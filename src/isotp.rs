@@ -0,0 +1,517 @@
+use crate::can::Frame;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// The longest message a first frame can announce without the 32-bit length escape.
+pub const MAX_MESSAGE: usize = 4095;
+const FRAME_LENGTH: usize = 8;
+const PADDING: u8 = 0x55;
+// How long to wait for the peer's flow control or next consecutive frame (N_Bs, N_Cr).
+const TIMEOUT: Duration = Duration::from_millis(1000);
+
+const SINGLE_FRAME: u8 = 0x0;
+const FIRST_FRAME: u8 = 0x1;
+const CONSECUTIVE_FRAME: u8 = 0x2;
+const FLOW_CONTROL: u8 = 0x3;
+
+const CONTINUE_TO_SEND: u8 = 0x0;
+const WAIT: u8 = 0x1;
+const OVERFLOW: u8 = 0x2;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum IsoTpError {
+    /// A consecutive frame arrived out of order, so the message was dropped.
+    WrongSequence { expected: u8, received: u8 },
+    /// The peer announced a message longer than we accept.
+    TooLong { length: usize },
+    /// The peer can't take the message we are sending.
+    Overflow,
+}
+
+impl fmt::Display for IsoTpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IsoTpError::WrongSequence { expected, received } => write!(
+                f,
+                "expected consecutive frame {}, received {}",
+                expected, received
+            ),
+            IsoTpError::TooLong { length } => {
+                write!(f, "message of {} bytes, at most {} are accepted", length, MAX_MESSAGE)
+            }
+            IsoTpError::Overflow => write!(f, "receiver reported an overflow"),
+        }
+    }
+}
+
+enum SendState {
+    /// The next frame may go out at the given time.
+    Ready(Instant),
+    /// The first frame or a block is sent, and the peer's flow control is awaited since the given time.
+    WaitingForFlowControl(Instant),
+}
+
+struct Sending {
+    payload: Vec<u8>,
+    offset: usize,
+    sequence: u8,
+    // Consecutive frames left in the block, or `None` if the peer set no block size.
+    block_remaining: Option<u8>,
+    separation: Duration,
+    state: SendState,
+}
+
+struct Receiving {
+    payload: Vec<u8>,
+    length: usize,
+    sequence: u8,
+    last_frame: Instant,
+}
+
+struct Repeat {
+    payload: Vec<u8>,
+    interval: Duration,
+    next_due: Instant,
+}
+
+/// One ISO 15765-2 link between a request and a response ID, on classic CAN frames.
+/// Segments outgoing messages, reassembles incoming ones and answers first frames
+/// with flow control. Nothing blocks: `poll` returns the frames to send, and `handle`
+/// takes the received ones.
+pub struct IsoTp {
+    tx_id: u32,
+    rx_id: u32,
+    extended: bool,
+    flow_control: Option<Frame>,
+    sending: Option<Sending>,
+    receiving: Option<Receiving>,
+    repeat: Option<Repeat>,
+}
+
+impl IsoTp {
+    pub fn new(tx_id: u32, rx_id: u32, extended: bool) -> IsoTp {
+        IsoTp {
+            tx_id,
+            rx_id,
+            extended,
+            flow_control: None,
+            sending: None,
+            receiving: None,
+            repeat: None,
+        }
+    }
+
+    /// Starts sending a message, abandoning one still being sent.
+    pub fn send(&mut self, payload: Vec<u8>) -> Result<(), IsoTpError> {
+        if payload.len() > MAX_MESSAGE {
+            return Err(IsoTpError::TooLong { length: payload.len() });
+        }
+        self.sending = Some(Sending {
+            payload,
+            offset: 0,
+            sequence: 0,
+            block_remaining: None,
+            separation: Duration::from_millis(0),
+            state: SendState::Ready(Instant::now()),
+        });
+        Ok(())
+    }
+
+    /// Sends `payload` `rate` times per second, whenever no other message is being sent.
+    pub fn repeat(&mut self, payload: Vec<u8>, rate: f32) -> Result<(), IsoTpError> {
+        if payload.len() > MAX_MESSAGE {
+            return Err(IsoTpError::TooLong { length: payload.len() });
+        }
+        self.repeat = Some(Repeat {
+            payload,
+            interval: Duration::from_secs_f32(1.0 / rate.max(0.01)),
+            next_due: Instant::now(),
+        });
+        Ok(())
+    }
+
+    /// Whether the frame belongs to this link, and should be passed to `handle`.
+    pub fn receives(&self, frame: &Frame) -> bool {
+        frame.extended == self.extended && frame.id == self.rx_id
+    }
+
+    /// The next frame to send, if any is due.
+    pub fn poll(&mut self, now: Instant) -> Option<Frame> {
+        if let Some(flow_control) = self.flow_control.take() {
+            return Some(flow_control);
+        }
+
+        if self
+            .receiving
+            .as_ref()
+            .is_some_and(|r| now.duration_since(r.last_frame) > TIMEOUT)
+        {
+            self.receiving = None;
+        }
+
+        if self.sending.is_none() {
+            let repeat = self.repeat.as_mut().filter(|r| r.next_due <= now)?;
+            repeat.next_due = now + repeat.interval;
+            let payload = repeat.payload.clone();
+            // Checked when the repeat was set up.
+            let _ = self.send(payload);
+        }
+
+        let sending = self.sending.as_mut()?;
+        if sending.offset == 0 {
+            let length = sending.payload.len();
+            if length < FRAME_LENGTH {
+                let mut data = vec![SINGLE_FRAME << 4 | length as u8];
+                data.extend_from_slice(&sending.payload);
+                self.sending = None;
+                return Some(self.frame(data));
+            }
+
+            let mut data = vec![FIRST_FRAME << 4 | (length >> 8) as u8, length as u8];
+            data.extend_from_slice(&sending.payload[..FRAME_LENGTH - 2]);
+            sending.offset = FRAME_LENGTH - 2;
+            sending.sequence = 1;
+            sending.state = SendState::WaitingForFlowControl(now);
+            return Some(self.frame(data));
+        }
+
+        match sending.state {
+            SendState::WaitingForFlowControl(since) => {
+                if now.duration_since(since) > TIMEOUT {
+                    self.sending = None;
+                }
+                return None;
+            }
+            SendState::Ready(at) if at > now => return None,
+            SendState::Ready(_) => {}
+        }
+
+        let end = (sending.offset + FRAME_LENGTH - 1).min(sending.payload.len());
+        let mut data = vec![CONSECUTIVE_FRAME << 4 | sending.sequence];
+        data.extend_from_slice(&sending.payload[sending.offset..end]);
+        sending.offset = end;
+        sending.sequence = (sending.sequence + 1) & 0x0F;
+        sending.block_remaining = sending.block_remaining.map(|n| n - 1);
+        sending.state = if sending.block_remaining == Some(0) {
+            SendState::WaitingForFlowControl(now)
+        } else {
+            SendState::Ready(now + sending.separation)
+        };
+        if end == sending.payload.len() {
+            self.sending = None;
+        }
+        Some(self.frame(data))
+    }
+
+    /// Takes a frame from the response ID. Returns a message once it is complete, or
+    /// the error that ended a transfer.
+    pub fn handle(&mut self, frame: &Frame, now: Instant) -> Option<Result<Vec<u8>, IsoTpError>> {
+        if !self.receives(frame) {
+            return None;
+        }
+        let data = &frame.data;
+        let pci = *data.first()?;
+
+        match pci >> 4 {
+            SINGLE_FRAME => {
+                let length = usize::from(pci & 0x0F);
+                if length == 0 || length >= data.len() {
+                    return None;
+                }
+                self.receiving = None;
+                Some(Ok(data[1..=length].to_vec()))
+            }
+            FIRST_FRAME => {
+                if data.len() < FRAME_LENGTH {
+                    return None;
+                }
+                // A length of 0 escapes to a 32-bit length, which is always too long.
+                let length = usize::from(pci & 0x0F) << 8 | usize::from(data[1]);
+                if length == 0 || length > MAX_MESSAGE {
+                    self.receiving = None;
+                    self.flow_control = Some(self.frame(vec![FLOW_CONTROL << 4 | OVERFLOW, 0, 0]));
+                    return Some(Err(IsoTpError::TooLong { length }));
+                }
+                // Anything that fits a single frame must be sent as one.
+                if length < FRAME_LENGTH {
+                    self.receiving = None;
+                    return None;
+                }
+                self.receiving = Some(Receiving {
+                    payload: data[2..].to_vec(),
+                    length,
+                    sequence: 1,
+                    last_frame: now,
+                });
+                // Ask for the rest in one block, without a separation time.
+                self.flow_control = Some(self.frame(vec![FLOW_CONTROL << 4 | CONTINUE_TO_SEND, 0, 0]));
+                None
+            }
+            CONSECUTIVE_FRAME => {
+                let receiving = self.receiving.as_mut()?;
+                let sequence = pci & 0x0F;
+                if sequence != receiving.sequence {
+                    let expected = receiving.sequence;
+                    self.receiving = None;
+                    return Some(Err(IsoTpError::WrongSequence {
+                        expected,
+                        received: sequence,
+                    }));
+                }
+
+                let remaining = receiving.length.saturating_sub(receiving.payload.len());
+                let end = data.len().min(remaining + 1);
+                receiving.payload.extend_from_slice(&data[1..end]);
+                receiving.sequence = (sequence + 1) & 0x0F;
+                receiving.last_frame = now;
+
+                if receiving.payload.len() < receiving.length {
+                    return None;
+                }
+                self.receiving.take().map(|r| Ok(r.payload))
+            }
+            FLOW_CONTROL => {
+                let sending = self.sending.as_mut()?;
+                if !matches!(sending.state, SendState::WaitingForFlowControl(_)) || data.len() < 3 {
+                    return None;
+                }
+                match pci & 0x0F {
+                    CONTINUE_TO_SEND => {
+                        sending.block_remaining = if data[1] == 0 { None } else { Some(data[1]) };
+                        sending.separation = separation_time(data[2]);
+                        sending.state = SendState::Ready(now);
+                        None
+                    }
+                    WAIT => {
+                        sending.state = SendState::WaitingForFlowControl(now);
+                        None
+                    }
+                    OVERFLOW => {
+                        self.sending = None;
+                        Some(Err(IsoTpError::Overflow))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn frame(&self, mut data: Vec<u8>) -> Frame {
+        data.resize(FRAME_LENGTH, PADDING);
+        Frame {
+            id: self.tx_id,
+            extended: self.extended,
+            data,
+        }
+    }
+}
+
+/// Decodes the STmin byte of a flow control frame.
+fn separation_time(value: u8) -> Duration {
+    match value {
+        0x00..=0x7F => Duration::from_millis(u64::from(value)),
+        0xF1..=0xF9 => Duration::from_micros(u64::from(value - 0xF0) * 100),
+        // Reserved values mean the longest separation time.
+        _ => Duration::from_millis(0x7F),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TX_ID: u32 = 0x7E0;
+    const RX_ID: u32 = 0x7E8;
+
+    fn frame(data: &[u8]) -> Frame {
+        Frame {
+            id: RX_ID,
+            extended: false,
+            data: data.to_vec(),
+        }
+    }
+
+    fn padded(data: &[u8]) -> Vec<u8> {
+        let mut data = data.to_vec();
+        data.resize(FRAME_LENGTH, PADDING);
+        data
+    }
+
+    #[test]
+    fn sends_single_frame() {
+        let mut link = IsoTp::new(TX_ID, RX_ID, false);
+        link.send(vec![0x09, 0x02]).unwrap();
+        let sent = link.poll(Instant::now()).unwrap();
+        assert_eq!(sent.id, TX_ID);
+        assert_eq!(sent.data, padded(&[0x02, 0x09, 0x02]));
+        assert!(link.poll(Instant::now()).is_none());
+    }
+
+    #[test]
+    fn receives_single_frame() {
+        let mut link = IsoTp::new(TX_ID, RX_ID, false);
+        let message = link.handle(&frame(&[0x03, 0x41, 0x0C, 0x1A, 0x55]), Instant::now());
+        assert_eq!(message, Some(Ok(vec![0x41, 0x0C, 0x1A])));
+    }
+
+    #[test]
+    fn ignores_other_ids() {
+        let mut link = IsoTp::new(TX_ID, RX_ID, false);
+        let other = Frame { id: 0x7E9, ..frame(&[0x01, 0x41]) };
+        assert!(!link.receives(&other));
+        assert_eq!(link.handle(&other, Instant::now()), None);
+    }
+
+    #[test]
+    fn sends_first_and_consecutive_frames_after_flow_control() {
+        let mut link = IsoTp::new(TX_ID, RX_ID, false);
+        let payload: Vec<u8> = (0..20).collect();
+        link.send(payload).unwrap();
+        let now = Instant::now();
+
+        let first = link.poll(now).unwrap();
+        assert_eq!(first.data, vec![0x10, 20, 0, 1, 2, 3, 4, 5]);
+        assert!(link.poll(now).is_none(), "waits for flow control");
+
+        assert_eq!(link.handle(&frame(&[0x30, 0, 0]), now), None);
+        assert_eq!(link.poll(now).unwrap().data, vec![0x21, 6, 7, 8, 9, 10, 11, 12]);
+        assert_eq!(link.poll(now).unwrap().data, vec![0x22, 13, 14, 15, 16, 17, 18, 19]);
+        assert!(link.poll(now).is_none());
+    }
+
+    #[test]
+    fn respects_block_size_and_separation_time() {
+        let mut link = IsoTp::new(TX_ID, RX_ID, false);
+        link.send((0..30).collect()).unwrap();
+        let now = Instant::now();
+        link.poll(now).unwrap();
+
+        // One frame per block, 10 ms apart.
+        link.handle(&frame(&[0x30, 2, 10]), now);
+        assert_eq!(link.poll(now).unwrap().data[0], 0x21);
+        assert!(link.poll(now).is_none(), "waits for the separation time");
+        assert_eq!(link.poll(now + Duration::from_millis(10)).unwrap().data[0], 0x22);
+        let later = now + Duration::from_millis(20);
+        assert!(link.poll(later).is_none(), "waits for flow control after the block");
+
+        link.handle(&frame(&[0x30, 0, 0]), later);
+        assert_eq!(link.poll(later).unwrap().data[0], 0x23);
+    }
+
+    #[test]
+    fn wait_flow_control_holds_the_transfer() {
+        let mut link = IsoTp::new(TX_ID, RX_ID, false);
+        link.send((0..10).collect()).unwrap();
+        let now = Instant::now();
+        link.poll(now).unwrap();
+
+        link.handle(&frame(&[0x31, 0, 0]), now);
+        assert!(link.poll(now).is_none());
+        link.handle(&frame(&[0x30, 0, 0]), now);
+        assert_eq!(link.poll(now).unwrap().data[0], 0x21);
+    }
+
+    #[test]
+    fn overflow_flow_control_aborts_sending() {
+        let mut link = IsoTp::new(TX_ID, RX_ID, false);
+        link.send((0..10).collect()).unwrap();
+        let now = Instant::now();
+        link.poll(now).unwrap();
+
+        assert_eq!(link.handle(&frame(&[0x32, 0, 0]), now), Some(Err(IsoTpError::Overflow)));
+        assert!(link.poll(now).is_none());
+    }
+
+    #[test]
+    fn flow_control_timeout_aborts_sending() {
+        let mut link = IsoTp::new(TX_ID, RX_ID, false);
+        link.send((0..10).collect()).unwrap();
+        let now = Instant::now();
+        link.poll(now).unwrap();
+
+        let later = now + TIMEOUT + Duration::from_millis(1);
+        assert!(link.poll(later).is_none());
+        assert_eq!(link.handle(&frame(&[0x30, 0, 0]), later), None);
+        assert!(link.poll(later).is_none());
+    }
+
+    #[test]
+    fn rejects_messages_too_long_to_send() {
+        let mut link = IsoTp::new(TX_ID, RX_ID, false);
+        let length = MAX_MESSAGE + 1;
+        assert_eq!(link.send(vec![0; length]), Err(IsoTpError::TooLong { length }));
+        assert_eq!(link.repeat(vec![0; length], 1.0), Err(IsoTpError::TooLong { length }));
+        assert!(link.poll(Instant::now()).is_none());
+    }
+
+    #[test]
+    fn receives_first_and_consecutive_frames() {
+        let mut link = IsoTp::new(TX_ID, RX_ID, false);
+        let now = Instant::now();
+
+        assert_eq!(link.handle(&frame(&[0x10, 10, 0, 1, 2, 3, 4, 5]), now), None);
+        let flow_control = link.poll(now).unwrap();
+        assert_eq!(flow_control.id, TX_ID);
+        assert_eq!(flow_control.data, padded(&[0x30, 0, 0]));
+
+        let message = link.handle(&frame(&[0x21, 6, 7, 8, 9, 0x55, 0x55, 0x55]), now);
+        assert_eq!(message, Some(Ok((0..10).collect())));
+    }
+
+    #[test]
+    fn drops_message_on_wrong_sequence() {
+        let mut link = IsoTp::new(TX_ID, RX_ID, false);
+        let now = Instant::now();
+        link.handle(&frame(&[0x10, 20, 0, 1, 2, 3, 4, 5]), now);
+
+        let message = link.handle(&frame(&[0x22, 6, 7, 8, 9, 10, 11, 12]), now);
+        assert_eq!(
+            message,
+            Some(Err(IsoTpError::WrongSequence {
+                expected: 1,
+                received: 2
+            }))
+        );
+        assert_eq!(link.handle(&frame(&[0x21, 6, 7, 8, 9, 10, 11, 12]), now), None);
+    }
+
+    #[test]
+    fn answers_too_long_first_frame_with_overflow() {
+        let mut link = IsoTp::new(TX_ID, RX_ID, false);
+        let now = Instant::now();
+        let message = link.handle(&frame(&[0x10, 0, 0, 0, 0x10, 0, 0, 0]), now);
+        assert_eq!(message, Some(Err(IsoTpError::TooLong { length: 0 })));
+        assert_eq!(link.poll(now).unwrap().data, padded(&[0x32, 0, 0]));
+    }
+
+    #[test]
+    fn ignores_first_frame_short_enough_for_a_single_frame() {
+        let mut link = IsoTp::new(TX_ID, RX_ID, false);
+        let now = Instant::now();
+        assert_eq!(link.handle(&frame(&[0x10, 0x03, 0, 1, 2, 3, 4, 5]), now), None);
+        assert!(link.poll(now).is_none());
+        assert_eq!(link.handle(&frame(&[0x21, 6, 7, 8, 9, 10, 11, 12]), now), None);
+    }
+
+    #[test]
+    fn consecutive_frame_timeout_drops_message() {
+        let mut link = IsoTp::new(TX_ID, RX_ID, false);
+        let now = Instant::now();
+        link.handle(&frame(&[0x10, 10, 0, 1, 2, 3, 4, 5]), now);
+        link.poll(now).unwrap();
+
+        let later = now + TIMEOUT + Duration::from_millis(1);
+        assert!(link.poll(later).is_none());
+        assert_eq!(link.handle(&frame(&[0x21, 6, 7, 8, 9]), later), None);
+    }
+
+    #[test]
+    fn decodes_separation_time() {
+        assert_eq!(separation_time(0x00), Duration::from_millis(0));
+        assert_eq!(separation_time(0x7F), Duration::from_millis(127));
+        assert_eq!(separation_time(0xF1), Duration::from_micros(100));
+        assert_eq!(separation_time(0xF9), Duration::from_micros(900));
+        assert_eq!(separation_time(0x80), Duration::from_millis(127));
+    }
+}
@@ -0,0 +1,332 @@
+use crate::can::{Frame, FrameFilter};
+use crate::decoder::Decoder;
+use crate::signal::{ByteOrder, DecodeError, Signal, ValueType};
+use std::time::{Duration, Instant};
+
+const PGN_MASK: u32 = 0x03FF_FF00;
+// PDU1 PGNs (PF below 240) carry a destination address in PS, which isn't part of the PGN.
+const PDU1_PGN_MASK: u32 = 0x03FF_0000;
+const SOURCE_ADDRESS_MASK: u32 = 0xFF;
+const PDU2_FORMAT: u32 = 240;
+const GLOBAL_ADDRESS: u32 = 0xFF;
+const DEFAULT_PRIORITY: u32 = 6;
+
+const TP_CONNECTION_MANAGEMENT: u32 = 0xEC00;
+const TP_DATA_TRANSFER: u32 = 0xEB00;
+const BROADCAST_ANNOUNCE: u8 = 0x20;
+// T1: the longest gap between the packets of a broadcast.
+const PACKET_TIMEOUT: Duration = Duration::from_millis(750);
+
+/// A suspect parameter, decoded from its PGN as `value = raw * factor + offset`.
+pub struct Spn {
+    pub spn: u32,
+    pub pgn: u32,
+    pub start_bit: u16, // Little endian, from the first data byte
+    pub length: u16,
+    pub factor: f32,
+    pub offset: f32,
+    pub unit: &'static str,
+}
+
+const SPNS: &[Spn] = &[
+    Spn { spn: 84, pgn: 65265, start_bit: 8, length: 16, factor: 1.0 / 256.0, offset: 0.0, unit: "km/h" }, // Wheel-based vehicle speed
+    Spn { spn: 91, pgn: 61443, start_bit: 8, length: 8, factor: 0.4, offset: 0.0, unit: "%" },   // Accelerator pedal position
+    Spn { spn: 92, pgn: 61443, start_bit: 16, length: 8, factor: 1.0, offset: 0.0, unit: "%" },  // Engine load at current speed
+    Spn { spn: 96, pgn: 65276, start_bit: 8, length: 8, factor: 0.4, offset: 0.0, unit: "%" },   // Fuel level
+    Spn { spn: 100, pgn: 65263, start_bit: 24, length: 8, factor: 4.0, offset: 0.0, unit: "kPa" }, // Engine oil pressure
+    Spn { spn: 102, pgn: 65270, start_bit: 8, length: 8, factor: 2.0, offset: 0.0, unit: "kPa" },  // Intake manifold pressure
+    Spn { spn: 105, pgn: 65270, start_bit: 16, length: 8, factor: 1.0, offset: -40.0, unit: "C" }, // Intake manifold temperature
+    Spn { spn: 110, pgn: 65262, start_bit: 0, length: 8, factor: 1.0, offset: -40.0, unit: "C" },  // Engine coolant temperature
+    Spn { spn: 168, pgn: 65271, start_bit: 32, length: 16, factor: 0.05, offset: 0.0, unit: "V" }, // Battery potential
+    Spn { spn: 175, pgn: 65262, start_bit: 16, length: 16, factor: 0.03125, offset: -273.0, unit: "C" }, // Engine oil temperature
+    Spn { spn: 183, pgn: 65266, start_bit: 0, length: 16, factor: 0.05, offset: 0.0, unit: "L/h" }, // Engine fuel rate
+    Spn { spn: 190, pgn: 61444, start_bit: 24, length: 16, factor: 0.125, offset: 0.0, unit: "rpm" }, // Engine speed
+    Spn { spn: 247, pgn: 65253, start_bit: 0, length: 32, factor: 0.05, offset: 0.0, unit: "h" },  // Engine total hours
+    Spn { spn: 513, pgn: 61444, start_bit: 16, length: 8, factor: 1.0, offset: -125.0, unit: "%" }, // Actual engine torque
+];
+
+pub fn find(spn: u32) -> Option<&'static Spn> {
+    SPNS.iter().find(|s| s.spn == spn)
+}
+
+/// Checks a raw value against the ranges J1939 reserves: from a top byte of 0xFB up,
+/// or the top two values of shorter parameters, the sender reports an error or no value.
+pub fn check(raw: u64, length: u16) -> Result<(), DecodeError> {
+    if raw <= largest_valid(length) {
+        return Ok(());
+    }
+    let error = if length >= 8 {
+        raw >> (length - 8) == 0xFE
+    } else {
+        raw == (1 << length) - 2
+    };
+    if error {
+        Err(DecodeError::SenderError)
+    } else {
+        Err(DecodeError::NotAvailable)
+    }
+}
+
+/// The largest raw value of a parameter that is a reading.
+pub fn largest_valid(length: u16) -> u64 {
+    if length >= 8 {
+        let rest = length - 8;
+        0xFA << rest | ((1 << rest) - 1)
+    } else if length >= 2 {
+        (1 << length) - 3
+    } else {
+        1
+    }
+}
+
+fn pgn(id: u32) -> u32 {
+    let mask = if (id >> 16) & 0xFF < PDU2_FORMAT {
+        PDU1_PGN_MASK
+    } else {
+        PGN_MASK
+    };
+    (id & mask) >> 8
+}
+
+/// Matches the frames carrying `pgn`, optionally only from one source address.
+pub fn filter(pgn: u32, source_address: Option<u8>) -> FrameFilter {
+    let pdu_format = (pgn >> 8) & 0xFF;
+    let pgn_mask = if pdu_format < PDU2_FORMAT {
+        PDU1_PGN_MASK
+    } else {
+        PGN_MASK
+    };
+    let (mask, source_address) = match source_address {
+        Some(address) => (pgn_mask | SOURCE_ADDRESS_MASK, u32::from(address)),
+        None => (pgn_mask, 0),
+    };
+    let id = (pgn << 8 | source_address) & mask;
+
+    FrameFilter {
+        extended: true,
+        first_id: id,
+        last_id: id,
+        mask,
+    }
+}
+
+impl Spn {
+    pub fn decoder(&self, source_address: Option<u8>) -> Decoder {
+        Decoder {
+            filter: filter(self.pgn, source_address),
+            signal: Signal {
+                start_bit: self.start_bit,
+                length: self.length,
+                byte_order: ByteOrder::LittleEndian,
+                value_type: ValueType::Unsigned,
+                factor: self.factor,
+                offset: self.offset,
+            },
+            multiplexer: None,
+            calibration: None,
+            raw: false,
+            j1939: true,
+        }
+    }
+}
+
+struct Broadcast {
+    source_address: u32,
+    pgn: u32,
+    size: usize,
+    sequence: u8,
+    data: Vec<u8>,
+    last_packet: Instant,
+}
+
+/// Reassembles PGNs longer than 8 bytes, sent with the broadcast announce message (BAM).
+pub struct Transport {
+    broadcasts: Vec<Broadcast>,
+}
+
+impl Transport {
+    pub fn new() -> Transport {
+        Transport {
+            broadcasts: Vec::new(),
+        }
+    }
+
+    /// Whether the frame belongs to the transport protocol, and should be passed to `handle`.
+    pub fn receives(&self, frame: &Frame) -> bool {
+        let pgn = pgn(frame.id);
+        frame.extended && (pgn == TP_CONNECTION_MANAGEMENT || pgn == TP_DATA_TRANSFER)
+    }
+
+    /// Takes a transport protocol frame. Returns the whole PGN, as a frame from its
+    /// sender, once all packets have arrived.
+    pub fn handle(&mut self, frame: &Frame, now: Instant) -> Option<Frame> {
+        self.broadcasts
+            .retain(|b| now.duration_since(b.last_packet) <= PACKET_TIMEOUT);
+
+        let pgn = pgn(frame.id);
+        let destination = (frame.id >> 8) & 0xFF;
+        let source_address = frame.id & SOURCE_ADDRESS_MASK;
+        let data = &frame.data;
+        // Connection mode transfers go to a single node, only broadcasts are reassembled.
+        if data.len() < 8 || destination != GLOBAL_ADDRESS {
+            return None;
+        }
+
+        if pgn == TP_CONNECTION_MANAGEMENT {
+            // A new announcement replaces an unfinished one from the same sender.
+            self.broadcasts.retain(|b| b.source_address != source_address);
+            if data[0] == BROADCAST_ANNOUNCE {
+                let size = usize::from(u16::from_le_bytes([data[1], data[2]]));
+                self.broadcasts.push(Broadcast {
+                    source_address,
+                    pgn: u32::from_le_bytes([data[5], data[6], data[7], 0]),
+                    size,
+                    sequence: 1,
+                    data: Vec::with_capacity(size),
+                    last_packet: now,
+                });
+            }
+            return None;
+        }
+
+        let index = self
+            .broadcasts
+            .iter()
+            .position(|b| b.source_address == source_address)?;
+        let broadcast = &mut self.broadcasts[index];
+        if data[0] != broadcast.sequence {
+            self.broadcasts.remove(index);
+            return None;
+        }
+        broadcast.data.extend_from_slice(&data[1..8]);
+        broadcast.sequence = broadcast.sequence.wrapping_add(1);
+        broadcast.last_packet = now;
+        if broadcast.data.len() < broadcast.size {
+            return None;
+        }
+
+        let mut broadcast = self.broadcasts.remove(index);
+        broadcast.data.truncate(broadcast.size);
+        let pdu_format = (broadcast.pgn >> 8) & 0xFF;
+        let destination = if pdu_format < PDU2_FORMAT {
+            GLOBAL_ADDRESS << 8
+        } else {
+            0
+        };
+        Some(Frame {
+            id: DEFAULT_PRIORITY << 26 | broadcast.pgn << 8 | destination | source_address,
+            extended: true,
+            data: broadcast.data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(id: u32, data: &[u8]) -> Frame {
+        Frame {
+            id,
+            extended: true,
+            data: data.to_vec(),
+        }
+    }
+
+    #[test]
+    fn scales_spns() {
+        let coolant = find(110).unwrap().decoder(None);
+        let temperatures = frame(0x18FE_EE00, &[0x5A, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(coolant.decode(&temperatures), Some(Ok(50.0)));
+
+        let engine_speed = find(190).unwrap().decoder(None);
+        let controller = frame(0x0CF0_0400, &[0, 0, 0, 0x20, 0x1C, 0, 0, 0]);
+        assert_eq!(engine_speed.decode(&controller), Some(Ok(900.0)));
+    }
+
+    #[test]
+    fn reserved_values_are_errors() {
+        let oil_pressure = find(100).unwrap().decoder(None);
+        let reading = |value: u8| oil_pressure.decode(&frame(0x18FE_EF00, &[0, 0, 0, value, 0, 0, 0, 0]));
+        assert_eq!(reading(0xFA), Some(Ok(1000.0)));
+        assert_eq!(reading(0xFE), Some(Err(DecodeError::SenderError)));
+        assert_eq!(reading(0xFF), Some(Err(DecodeError::NotAvailable)));
+
+        let engine_speed = find(190).unwrap().decoder(None);
+        let reading = |high: u8| engine_speed.decode(&frame(0x0CF0_0400, &[0, 0, 0, 0x34, high, 0, 0, 0]));
+        assert_eq!(reading(0xFA), Some(Ok(8006.5)));
+        assert_eq!(reading(0xFE), Some(Err(DecodeError::SenderError)));
+        assert_eq!(reading(0xFF), Some(Err(DecodeError::NotAvailable)));
+
+        assert_eq!(largest_valid(2), 1);
+        assert_eq!(check(2, 2), Err(DecodeError::SenderError));
+        assert_eq!(check(3, 2), Err(DecodeError::NotAvailable));
+    }
+
+    #[test]
+    fn filters_pdu2_by_source_address() {
+        let any = filter(65262, None);
+        let engine = filter(65262, Some(0x00));
+        let other_sender = frame(0x18FE_EE03, &[]);
+        assert!(any.matches(&other_sender));
+        assert!(!engine.matches(&other_sender));
+        assert!(engine.matches(&frame(0x18FE_EE00, &[])));
+        assert!(!engine.matches(&frame(0x18FE_EF00, &[])));
+    }
+
+    #[test]
+    fn filters_pdu1_ignoring_destination() {
+        let proprietary = filter(0xEF00, Some(0x21));
+        assert!(proprietary.matches(&frame(0x18EF_1021, &[])));
+        assert!(proprietary.matches(&frame(0x18EF_FF21, &[])));
+        assert!(!proprietary.matches(&frame(0x18EF_1022, &[])));
+        assert!(!proprietary.matches(&frame(0x18EE_1021, &[])));
+        assert_eq!(pgn(0x18EF_1021), 0xEF00);
+        assert_eq!(pgn(0x18FE_EE03), 0xFEEE);
+    }
+
+    #[test]
+    fn reassembles_broadcasts() {
+        let mut transport = Transport::new();
+        let now = Instant::now();
+        let announce = frame(0x1CEC_FF00, &[BROADCAST_ANNOUNCE, 10, 0, 2, 0xFF, 0xE3, 0xFE, 0x00]);
+        assert!(transport.receives(&announce));
+        assert!(!transport.receives(&frame(0x18FE_EE00, &[])));
+
+        assert!(transport.handle(&announce, now).is_none());
+        assert!(transport.handle(&frame(0x1CEB_FF00, &[1, 1, 2, 3, 4, 5, 6, 7]), now).is_none());
+        let message = transport
+            .handle(&frame(0x1CEB_FF00, &[2, 8, 9, 10, 0xFF, 0xFF, 0xFF, 0xFF]), now)
+            .unwrap();
+        assert_eq!(message.id, 0x18FE_E300);
+        assert!(message.extended);
+        assert_eq!(message.data, vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+    }
+
+    #[test]
+    fn drops_broadcasts_out_of_sequence_or_too_slow() {
+        let mut transport = Transport::new();
+        let now = Instant::now();
+        let announce = frame(0x1CEC_FF00, &[BROADCAST_ANNOUNCE, 10, 0, 2, 0xFF, 0xE3, 0xFE, 0x00]);
+        let first = frame(0x1CEB_FF00, &[1, 1, 2, 3, 4, 5, 6, 7]);
+        let second = frame(0x1CEB_FF00, &[2, 8, 9, 10, 0xFF, 0xFF, 0xFF, 0xFF]);
+
+        transport.handle(&announce, now);
+        assert!(transport.handle(&second, now).is_none());
+        assert!(transport.handle(&first, now).is_none());
+
+        transport.handle(&announce, now);
+        transport.handle(&first, now);
+        let later = now + PACKET_TIMEOUT + Duration::from_millis(1);
+        assert!(transport.handle(&second, later).is_none());
+    }
+
+    #[test]
+    fn ignores_connection_mode_transfers() {
+        let mut transport = Transport::new();
+        let now = Instant::now();
+        transport.handle(&frame(0x1CEC_2100, &[BROADCAST_ANNOUNCE, 9, 0, 2, 0xFF, 0xE3, 0xFE, 0x00]), now);
+        transport.handle(&frame(0x1CEB_2100, &[1, 1, 2, 3, 4, 5, 6, 7]), now);
+        assert!(transport.handle(&frame(0x1CEB_2100, &[2, 8, 9, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]), now).is_none());
+    }
+}
@@ -19,29 +19,119 @@ use embedded_graphics::pixelcolor::Rgb888;
 
 use embedded_graphics::{prelude::*, primitives::Rectangle};
 use embedded_graphics_simulator::{
-    OutputSettingsBuilder, SimulatorDisplay, SimulatorEvent, Window,
+    sdl2::Keycode, OutputSettingsBuilder, SimulatorDisplay, SimulatorEvent, Window,
 };
 #[cfg(not(feature = "colors"))]
 use embedded_graphics_simulator::BinaryColorTheme;
-use gauge::{dial::Dial, textgauge::TextGauge, Digits, SetValue};
-use socketcan::CANSocket;
-use std::collections::HashMap;
+use gauge::{
+    dial::Dial, labelgauge::LabelGauge, lamp::Lamp, textgauge::TextGauge, Digits, SetValue, Status,
+};
 use std::fs::File;
 use std::io::Read;
-use std::time::Duration;
-use crate::config::{Config, Gauge, GaugeType};
+use std::time::{Duration, Instant};
+use crate::can::{CanSocket, Frame};
+use crate::channels::Channels;
+use crate::config::{Config, GaugeType, Waveform};
+use crate::dbc::Dbc;
+use crate::decoder::Decoder;
+use crate::expr::Expression;
+use crate::filter::FilterChain;
+use crate::isotp::IsoTp;
+use crate::j1939::Transport;
+use crate::obd::ObdPoller;
+use crate::recorder::Recorder;
+use crate::signal::DecodeError;
+use crate::source::{generator::Generator, replay::Replay, slcan::Slcan, FrameSource};
+use crate::uds::UdsClient;
+use crate::validation::FrameCheck;
+use crate::units::Conversion;
 
 mod gauge;
+mod calibration;
+mod can;
+mod channels;
 mod config;
+mod dbc;
+mod decoder;
+mod expr;
+mod filter;
+mod isotp;
+mod j1939;
+mod obd;
+mod recorder;
+mod signal;
+mod source;
+mod uds;
+mod validation;
+mod units;
+
+/// Limits outside of which a gauge shows an alarm, checked on separately filtered values.
+struct Alarm {
+    low: Option<f32>,
+    high: Option<f32>,
+    filters: FilterChain,
+}
+
+impl Alarm {
+    fn check(&mut self, value: f32, now: Instant) -> bool {
+        let value = self.filters.apply(value, now);
+        self.low.is_some_and(|low| value < low) || self.high.is_some_and(|high| value > high)
+    }
+}
 
 struct GaugeSetup<'a> {
     gauge: gauge::Gauge<'a>,
-    config: &'a Gauge,
+    channel: String,
+    conversion: Conversion,
+    filters: FilterChain,
+    alarm: Option<Alarm>,
+    timeout: Option<Duration>,
+    last_update: Instant,
 }
 
 impl GaugeSetup<'_> {
-    fn new<'a>(gauge: gauge::Gauge<'a>, config: &'a Gauge) -> GaugeSetup<'a> {
-        GaugeSetup { gauge, config }
+    fn new(
+        gauge: gauge::Gauge<'_>,
+        channel: String,
+        conversion: Conversion,
+        filters: FilterChain,
+        alarm: Option<Alarm>,
+        timeout: Option<Duration>,
+    ) -> GaugeSetup<'_> {
+        GaugeSetup {
+            gauge,
+            channel,
+            conversion,
+            filters,
+            alarm,
+            timeout,
+            last_update: Instant::now(),
+        }
+    }
+
+    /// Marks the gauge stale once its timeout passes without an update.
+    fn check_timeout(&mut self, now: Instant) {
+        if let Some(timeout) = self.timeout {
+            if now.duration_since(self.last_update) > timeout {
+                self.gauge.set_status(Status::Stale);
+            }
+        }
+    }
+
+    fn update(&mut self, value: Result<f32, DecodeError>) {
+        let now = Instant::now();
+        self.last_update = now;
+        match value {
+            Ok(value) => {
+                let value = self.conversion.apply(value);
+                if let Some(alarm) = self.alarm.as_mut() {
+                    self.gauge.set_alarm(alarm.check(value, now));
+                }
+                self.gauge.set_value(self.filters.apply(value, now));
+            }
+            Err(DecodeError::Integrity) | Err(DecodeError::SenderError) => self.gauge.set_status(Status::Fault),
+            Err(_) => self.gauge.set_status(Status::Invalid),
+        }
     }
 }
 
@@ -51,6 +141,7 @@ fn main() -> Result<(), std::convert::Infallible> {
     let mut file_content = String::new();
     let _bytes_read = file.read_to_string(&mut file_content).unwrap();
     let config: Config = toml::from_str(&&file_content).unwrap();
+    let dbc = Dbc::load(&config.dbc_files);
 
     #[cfg(not(feature = "colors"))]
     let mut display: SimulatorDisplay<BinaryColor> = SimulatorDisplay::new(Size::new(config.width, config.height));
@@ -68,13 +159,94 @@ fn main() -> Result<(), std::convert::Infallible> {
 
     let mut window = Window::new("m8r", &output_settings);
 
-    let mut gauges: HashMap<u32, Vec<GaugeSetup>> = HashMap::new();
-    for gauge_config in config.gauges.iter() {
-        if !gauges.contains_key(&gauge_config.frame_id) {
-            gauges.insert(gauge_config.frame_id, Vec::new());
+    let mut channels = Channels::new();
+    for channel_config in config.channels.iter() {
+        let name = channel_config.name.clone();
+        match &channel_config.expression {
+            Some(expression) => {
+                let expression = Expression::parse(expression)
+                    .unwrap_or_else(|e| panic!("Channel {}: {}", name, e));
+                channels.add_computed(name, expression);
+            }
+            None => {
+                let (decoder, _) =
+                    Decoder::new(&channel_config.source, &name, config.slot_size, &dbc);
+                channels.add_input(name, decoder);
+            }
         }
+    }
 
-        let list = gauges.get_mut(&gauge_config.frame_id).unwrap();
+    if config.replay.is_some() && config.generator.is_some() {
+        panic!("Either replay or generator can be configured, not both");
+    }
+    let mut generator = config
+        .generator
+        .as_ref()
+        .map(|generator| Generator::new(generator.rate.unwrap_or(20.0)));
+    // Generated frames are whole messages, which don't go through transport protocols.
+    let generating = generator.is_some();
+    // What the generator sends for the channel each gauge shows.
+    let mut generated: Vec<(String, Waveform, f32, f32, Conversion)> = Vec::new();
+    let mut gauges: Vec<GaugeSetup> = Vec::new();
+    for (index, gauge_config) in config.gauges.iter().enumerate() {
+        let (channel, dbc_signal) = match &gauge_config.channel {
+            Some(channel) => (channel.clone(), dbc.find(channel).map(|(_, s)| s)),
+            None => {
+                let (decoder, dbc_signal) = Decoder::new(
+                    &gauge_config.source,
+                    &gauge_config.title,
+                    config.slot_size,
+                    &dbc,
+                );
+                // Unnamed gauges get a name that can't clash with a channel.
+                let channel = gauge_config
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| format!("#{}", index));
+                channels.add_input(channel.clone(), decoder);
+                (channel, dbc_signal)
+            }
+        };
+
+        let source_unit = gauge_config
+            .source_unit
+            .as_deref()
+            // DBC signals without a unit have an empty one.
+            .or_else(|| dbc_signal.map(|s| s.unit.as_str()).filter(|unit| !unit.is_empty()))
+            .or_else(|| {
+                obd::parse_channel(&channel)
+                    .and_then(obd::find)
+                    .map(|pid| pid.unit)
+            })
+            .or_else(|| gauge_config.source.spn.and_then(j1939::find).map(|spn| spn.unit));
+        let unit = gauge_config.unit.as_deref().or(source_unit).unwrap_or("");
+        // Labels are looked up by the decoded value, which converting or smoothing would change.
+        let labelled = matches!(gauge_config.gauge, GaugeType::LabelGauge);
+        let conversion = match source_unit {
+            Some(source_unit) if !labelled => Conversion::between(source_unit, unit).unwrap_or_else(|| {
+                panic!("Gauge {} can't convert {} to {}", gauge_config.title, source_unit, unit)
+            }),
+            _ => Conversion::identity(),
+        };
+        let min_value = gauge_config
+            .min_value
+            .or_else(|| dbc_signal.map(|s| conversion.apply(s.min)));
+        let max_value = gauge_config
+            .max_value
+            .or_else(|| dbc_signal.map(|s| conversion.apply(s.max)));
+
+        if generator.is_some() {
+            let (waveform, low, high) = match gauge_config.gauge {
+                GaugeType::Lamp => (Waveform::Square { period: None, duty: None }, 0.0, 1.0),
+                _ => (
+                    Waveform::Sine { period: None },
+                    min_value.unwrap_or(0.0),
+                    max_value.unwrap_or(100.0),
+                ),
+            };
+            let waveform = gauge_config.waveform.clone().unwrap_or(waveform);
+            generated.push((channel.clone(), waveform, low, high, conversion));
+        }
 
         let digits = match gauge_config.digits {
             0 => Digits::None,
@@ -82,13 +254,13 @@ fn main() -> Result<(), std::convert::Infallible> {
             _ => Digits::Two,
         };
 
-        match gauge_config.gauge {
+        let gauge = match gauge_config.gauge {
             GaugeType::Dial => {
-                let dial = Dial::new(
+                let mut dial = Dial::new(
                     &gauge_config.title,
-                    gauge_config.min_value.unwrap(),
-                    gauge_config.max_value.unwrap(),
-                    gauge_config.min_value.unwrap(),
+                    min_value.unwrap(),
+                    max_value.unwrap(),
+                    min_value.unwrap(),
                     digits,
                     Rectangle::new(
                         Point::new(gauge_config.point.x, gauge_config.point.y),
@@ -97,12 +269,13 @@ fn main() -> Result<(), std::convert::Infallible> {
                     gauge_config.indicators.as_ref().unwrap().as_slice(),
                     &config,
                 );
-                list.push(GaugeSetup::new(gauge::Gauge::Dial(dial), &gauge_config));
+                dial.peak_hold = gauge_config.peak_hold;
+                gauge::Gauge::Dial(dial)
             }
             GaugeType::TextGauge => {
-                let textgauge = TextGauge::new(
+                let mut textgauge = TextGauge::new(
                     &gauge_config.title,
-                    &gauge_config.unit,
+                    unit,
                     0.0,
                     digits,
                     Rectangle::new(
@@ -111,16 +284,190 @@ fn main() -> Result<(), std::convert::Infallible> {
                     ),
                     &config,
                 );
-                list.push(GaugeSetup::new(
-                    gauge::Gauge::TextGauge(textgauge),
-                    &gauge_config,
-                ));
+                textgauge.peak_hold = gauge_config.peak_hold;
+                gauge::Gauge::TextGauge(textgauge)
+            }
+            GaugeType::Lamp => gauge::Gauge::Lamp(Lamp::new(
+                &gauge_config.title,
+                gauge_config.active_low.unwrap_or(false),
+                Rectangle::new(
+                    Point::new(gauge_config.point.x, gauge_config.point.y),
+                    Size::new(gauge_config.size.width, gauge_config.size.height),
+                ),
+                &config,
+            )),
+            GaugeType::LabelGauge => {
+                let labels = match &gauge_config.value_labels {
+                    Some(labels) => labels.iter().map(|l| (l.value, l.label.clone())).collect(),
+                    // DBC value tables label the raw values, as the gauge's decoder scales them.
+                    None => dbc_signal
+                        .map(|s| {
+                            let decoder = channels.inputs().find(|(name, _)| *name == channel).map(|(_, d)| d);
+                            s.values
+                                .iter()
+                                .map(|(raw, label)| {
+                                    let value = match decoder {
+                                        Some(decoder) => decoder.scale(*raw as f32),
+                                        None => *raw as f32 * s.signal.factor + s.signal.offset,
+                                    };
+                                    (value.round() as i64, label.clone())
+                                })
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                };
+                gauge::Gauge::LabelGauge(LabelGauge::new(
+                    &gauge_config.title,
+                    labels,
+                    gauge_config.fallback_label.as_deref(),
+                    Rectangle::new(
+                        Point::new(gauge_config.point.x, gauge_config.point.y),
+                        Size::new(gauge_config.size.width, gauge_config.size.height),
+                    ),
+                    &config,
+                ))
+            }
+        };
+
+        let filters = if labelled {
+            FilterChain::new(&[])
+        } else {
+            FilterChain::new(&gauge_config.filters)
+        };
+        let alarm = gauge_config.alarm.as_ref().map(|alarm| Alarm {
+            low: alarm.low,
+            high: alarm.high,
+            filters: FilterChain::new(&alarm.filters),
+        });
+        let timeout = gauge_config.timeout.map(Duration::from_secs_f32);
+        gauges.push(GaugeSetup::new(gauge, channel, conversion, filters, alarm, timeout));
+    }
+
+    let obd_config = config.obd.as_ref();
+    let mut obd_poller = ObdPoller::new(
+        obd_config
+            .and_then(|o| o.request_id)
+            .unwrap_or(obd::BROADCAST_REQUEST_ID),
+    );
+    for pid in obd_config.iter().flat_map(|o| o.pids.iter()) {
+        obd_poller.add(pid.pid, pid.rate);
+    }
+
+    let mut links: Vec<IsoTp> = Vec::new();
+    for link_config in config.isotp.iter() {
+        let mut link = IsoTp::new(link_config.request_id, link_config.response_id, link_config.extended);
+        if let Some(request) = &link_config.request {
+            link.repeat(request.clone(), link_config.rate.unwrap_or(1.0))
+                .unwrap_or_else(|e| panic!("ISO-TP request to {:#X}: {}", link_config.request_id, e));
+        }
+        links.push(link);
+    }
+
+    let mut uds_clients: Vec<UdsClient> = Vec::new();
+    let sources = config
+        .channels
+        .iter()
+        .map(|c| &c.source)
+        .chain(config.gauges.iter().map(|g| &g.source));
+    for uds in sources.filter_map(|s| s.uds.as_ref()) {
+        let index = match uds_clients.iter().position(|c| c.serves(uds)) {
+            Some(index) => index,
+            None => {
+                uds_clients.push(UdsClient::new(uds.request_id, uds.response_id, uds.extended));
+                uds_clients.len() - 1
             }
+        };
+        if let Some(session) = uds.session {
+            uds_clients[index].set_session(session);
         }
+        uds_clients[index].add(uds.did, uds.rate.unwrap_or(5.0));
+    }
+
+    let mut transport = Transport::new();
+
+    let mut frame_checks: Vec<FrameCheck> = config.frame_checks.iter().map(FrameCheck::new).collect();
+    for name in frame_checks.iter().filter_map(|c| c.name.clone()) {
+        channels.add_value(name);
+    }
+
+    // Channels may also refer directly to DBC signals and OBD-II PIDs.
+    let mut undefined = channels.undefined();
+    undefined.extend(
+        gauges
+            .iter()
+            .filter(|g| !channels.contains(&g.channel))
+            .map(|g| g.channel.clone()),
+    );
+    if let Some(channel) = &config.peak_reset_channel {
+        if !channels.contains(channel) && !undefined.contains(channel) {
+            undefined.push(channel.clone());
+        }
+    }
+    for name in undefined {
+        if let Some(pid) = obd::parse_channel(&name) {
+            let pid = obd::find(pid).unwrap_or_else(|| panic!("Unsupported OBD-II PID {}", name));
+            if !obd_poller.contains(pid.pid) {
+                obd_poller.add(pid.pid, obd_config.and_then(|o| o.default_rate).unwrap_or(5.0));
+            }
+            channels.add_input(name, pid.decoder());
+            continue;
+        }
+
+        let (message, dbc_signal) = dbc
+            .find(&name)
+            .unwrap_or_else(|| panic!("Unknown channel {}", name));
+        channels.add_input(name, Decoder::from_dbc(message, dbc_signal));
+    }
+
+    let peak_reset_key = config.peak_reset_key.as_deref().unwrap_or("R");
+    let peak_reset_key = Keycode::from_name(peak_reset_key)
+        .unwrap_or_else(|| panic!("Unknown peak_reset_key {}", peak_reset_key));
+
+    if let Some(generator) = generator.as_mut() {
+        // Every input channel is generated, so computed channels and the gauges showing
+        // them move too. Inputs no gauge shows span their DBC or signal range.
+        for (name, decoder) in channels.inputs() {
+            match generated.iter().find(|(channel, ..)| channel == name) {
+                Some((_, waveform, low, high, conversion)) => {
+                    generator.add(decoder, waveform.clone(), *low, *high, *conversion)
+                }
+                None => {
+                    let (low, high) = dbc
+                        .find(name)
+                        .map(|(_, s)| (s.min, s.max))
+                        .filter(|(min, max)| min < max)
+                        .unwrap_or_else(|| source::generator::range(decoder));
+                    let waveform = Waveform::Sine { period: None };
+                    generator.add(decoder, waveform, low, high, Conversion::identity());
+                }
+            }
+        }
+        // Generated frames carry no alive counters or checksums.
+        frame_checks.clear();
     }
 
     // TODO: Set up filter, to filter out frames not relevant.
-    let socket = CANSocket::open(&config.interface).unwrap();
+    let mut source: Box<dyn FrameSource> = match (&config.replay, generator, &config.slcan, &config.interface) {
+        (Some(replay), _, _, _) => {
+            let frames = source::read_recording(replay)
+                .unwrap_or_else(|e| panic!("Could not read {}: {}", replay.file, e));
+            Box::new(Replay::new(frames, replay.speed.unwrap_or(1.0), replay.looping))
+        }
+        (None, Some(generator), _, _) => Box::new(generator),
+        (None, None, Some(slcan), _) => Box::new(
+            Slcan::open(&slcan.device, slcan.bitrate, slcan.baud.unwrap_or(115_200))
+                .unwrap_or_else(|e| panic!("Could not open {}: {}", slcan.device, e)),
+        ),
+        (None, None, None, Some(interface)) => Box::new(CanSocket::open(interface, config.fd).unwrap()),
+        (None, None, None, None) => panic!("Either interface, slcan, replay or generator must be configured"),
+    };
+    let mut recorder = config.record.as_ref().map(|record| {
+        let interface = match &config.slcan {
+            Some(_) => "slcan0",
+            None => config.interface.as_deref().unwrap_or("replay"),
+        };
+        Recorder::new(record, interface).unwrap_or_else(|e| panic!("Could not start recording: {}", e))
+    });
     let target_fps = 30;
     let time_per_frame = Duration::from_millis(1000 / target_fps);
 
@@ -130,43 +477,115 @@ fn main() -> Result<(), std::convert::Infallible> {
     let background = BinaryColor::Off;
 
     'running: loop {
-        let frame_start = std::time::Instant::now();
+        let frame_start = Instant::now();
 
         display.clear(background)?;
 
-        for (_, gauge_slots) in gauges.iter() {
-            for gauge_setup in gauge_slots.iter() {
-                gauge_setup.gauge.draw(&mut display)?;
-            }
+        for gauge_setup in gauges.iter_mut() {
+            gauge_setup.check_timeout(frame_start);
+            gauge_setup.gauge.draw(&mut display)?;
         }
 
         window.update(&display);
 
-        if window.events().any(|e| e == SimulatorEvent::Quit) {
-            break 'running Ok(());
+        if let Some(recorder) = recorder.as_mut() {
+            let _ = recorder.flush();
+        }
+
+        for event in window.events() {
+            match event {
+                SimulatorEvent::Quit => break 'running Ok(()),
+                SimulatorEvent::KeyDown { keycode, .. } if keycode == peak_reset_key => {
+                    for gauge_setup in gauges.iter_mut() {
+                        gauge_setup.gauge.reset_peaks();
+                    }
+                }
+                _ => {}
+            }
         }
 
         loop {
             let time_to_next_frame =
-                time_per_frame.checked_sub(std::time::Instant::now().duration_since(frame_start));
+                time_per_frame.checked_sub(Instant::now().duration_since(frame_start));
             match time_to_next_frame {
                 Some(time) => {
                     if time.as_millis() > 0 {
-                        socket.set_read_timeout(time).unwrap();
-                        let frame = socket.read_frame();
+                        let now = Instant::now();
+                        if let Some(request) = obd_poller.poll(now) {
+                            // A failed request is retried once it is due again.
+                            let _ = source.write_frame(&request);
+                        }
+                        for link in links.iter_mut() {
+                            while let Some(frame) = link.poll(now) {
+                                let _ = source.write_frame(&frame);
+                            }
+                        }
+                        for client in uds_clients.iter_mut() {
+                            while let Some(frame) = client.poll(now) {
+                                let _ = source.write_frame(&frame);
+                            }
+                        }
+
+                        let frame = source.read_frame(time);
                         match frame {
                             Result::Ok(f) => {
-                                let frame_gauges = gauges.get_mut(&f.id());
-                                match frame_gauges {
-                                    Some(fgauges) => {
-                                        for gauge_setup in fgauges.iter_mut() {
-                                            let slot_start =
-                                                (gauge_setup.config.slot_id - 1) * config.slot_size;
-                                            let value = gauge_setup.config.data_type.value(&f.data(), slot_start);
-                                            gauge_setup.gauge.set_value(value);
+                                if let Some(recorder) = recorder.as_mut() {
+                                    // Losing the recording shouldn't stop the gauges.
+                                    let _ = recorder.record(&f);
+                                }
+                                obd_poller.handle(&f);
+                                // Transport protocols hand on whole messages, once complete.
+                                // Transfers that fail are dropped, the peer will send again.
+                                // OBD-II replies can share an ECU's response ID, and are read as they arrive.
+                                let now = Instant::now();
+                                let mut changed = Vec::new();
+                                let message = if generating {
+                                    Some(f)
+                                } else if let Some(link) = links.iter_mut().find(|l| l.receives(&f)) {
+                                    changed.extend(channels.update_raw(&f));
+                                    match link.handle(&f, now) {
+                                        Some(Ok(message)) => Some(Frame { data: message, ..f }),
+                                        _ => None,
+                                    }
+                                } else if let Some(client) = uds_clients.iter_mut().find(|c| c.receives(&f)) {
+                                    changed.extend(channels.update_raw(&f));
+                                    client.handle(&f, now)
+                                } else if transport.receives(&f) {
+                                    transport.handle(&f, now)
+                                } else {
+                                    Some(f)
+                                };
+                                if let Some(f) = message {
+                                    // Frames failing their check are dropped, and fault their gauges.
+                                    let failed_check = frame_checks
+                                        .iter_mut()
+                                        .find(|c| c.filter.matches(&f))
+                                        .and_then(|c| if c.check(&f) { None } else { Some(c) });
+                                    match failed_check {
+                                        Some(check) => {
+                                            changed.extend(channels.fail(&f, DecodeError::Integrity));
+                                            if let Some(name) = &check.name {
+                                                changed.extend(channels.set(name, Ok(check.failures as f32)));
+                                            }
                                         }
+                                        None => changed.extend(channels.update(&f)),
+                                    }
+                                }
+                                let reset_peaks = config.peak_reset_channel.as_ref().is_some_and(|channel| {
+                                    changed.contains(channel)
+                                        && matches!(channels.value(channel), Some(Ok(value)) if value != 0.0)
+                                });
+                                if reset_peaks {
+                                    for gauge_setup in gauges.iter_mut() {
+                                        gauge_setup.gauge.reset_peaks();
+                                    }
+                                }
+                                let changed_gauges =
+                                    gauges.iter_mut().filter(|g| changed.contains(&g.channel));
+                                for gauge_setup in changed_gauges {
+                                    if let Some(value) = channels.value(&gauge_setup.channel) {
+                                        gauge_setup.update(value);
                                     }
-                                    None => continue,
                                 }
                             }
                             Result::Err(_) => continue,
@@ -0,0 +1,199 @@
+use crate::can::{Frame, FrameFilter};
+use crate::decoder::Decoder;
+use crate::signal::{ByteOrder, Multiplexer, Signal, ValueType};
+use std::time::{Duration, Instant};
+
+/// The functional address all emission related ECUs listen to.
+pub const BROADCAST_REQUEST_ID: u32 = 0x7DF;
+const FIRST_RESPONSE_ID: u32 = 0x7E8;
+const LAST_RESPONSE_ID: u32 = 0x7EF;
+
+const SHOW_CURRENT_DATA: u8 = 0x01;
+const RESPONSE_OFFSET: u8 = 0x40;
+// Don't wait forever for an ECU that doesn't support a PID.
+const RESPONSE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// A mode 01 PID, decoded as `value = data * factor + offset` with data being A or 256A+B.
+pub struct Pid {
+    pub pid: u8,
+    pub bytes: u8,
+    pub factor: f32,
+    pub offset: f32,
+    pub unit: &'static str,
+}
+
+const PIDS: &[Pid] = &[
+    Pid { pid: 0x04, bytes: 1, factor: 100.0 / 255.0, offset: 0.0, unit: "%" }, // Engine load
+    Pid { pid: 0x05, bytes: 1, factor: 1.0, offset: -40.0, unit: "C" },         // Coolant temperature
+    Pid { pid: 0x0B, bytes: 1, factor: 1.0, offset: 0.0, unit: "kPa" },         // Intake manifold pressure
+    Pid { pid: 0x0C, bytes: 2, factor: 0.25, offset: 0.0, unit: "rpm" },        // Engine speed
+    Pid { pid: 0x0D, bytes: 1, factor: 1.0, offset: 0.0, unit: "km/h" },        // Vehicle speed
+    Pid { pid: 0x0E, bytes: 1, factor: 0.5, offset: -64.0, unit: "deg" },       // Timing advance
+    Pid { pid: 0x0F, bytes: 1, factor: 1.0, offset: -40.0, unit: "C" },         // Intake air temperature
+    Pid { pid: 0x10, bytes: 2, factor: 0.01, offset: 0.0, unit: "g/s" },        // MAF air flow rate
+    Pid { pid: 0x11, bytes: 1, factor: 100.0 / 255.0, offset: 0.0, unit: "%" }, // Throttle position
+    Pid { pid: 0x2F, bytes: 1, factor: 100.0 / 255.0, offset: 0.0, unit: "%" }, // Fuel tank level
+    Pid { pid: 0x33, bytes: 1, factor: 1.0, offset: 0.0, unit: "kPa" },         // Barometric pressure
+    Pid { pid: 0x42, bytes: 2, factor: 0.001, offset: 0.0, unit: "V" },         // Control module voltage
+    Pid { pid: 0x46, bytes: 1, factor: 1.0, offset: -40.0, unit: "C" },         // Ambient air temperature
+    Pid { pid: 0x5C, bytes: 1, factor: 1.0, offset: -40.0, unit: "C" },         // Engine oil temperature
+];
+
+pub fn find(pid: u8) -> Option<&'static Pid> {
+    PIDS.iter().find(|p| p.pid == pid)
+}
+
+/// Parses channel names like `obd:0x05`.
+pub fn parse_channel(name: &str) -> Option<u8> {
+    let pid = name.strip_prefix("obd:")?;
+    match pid.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16).ok(),
+        None => pid.parse().ok(),
+    }
+}
+
+impl Pid {
+    /// Decodes single frame responses: [length, 0x41, PID, A, B, ...].
+    pub fn decoder(&self) -> Decoder {
+        let response = u64::from(SHOW_CURRENT_DATA + RESPONSE_OFFSET);
+
+        Decoder {
+            filter: FrameFilter {
+                extended: false,
+                first_id: FIRST_RESPONSE_ID,
+                last_id: LAST_RESPONSE_ID,
+                mask: 0x1FFF_FFFF,
+            },
+            signal: Signal {
+                start_bit: 3 * 8 + 7,
+                length: u16::from(self.bytes) * 8,
+                byte_order: ByteOrder::BigEndian,
+                value_type: ValueType::Unsigned,
+                factor: self.factor,
+                offset: self.offset,
+            },
+            multiplexer: Some(Multiplexer {
+                signal: Signal {
+                    start_bit: 8 + 7,
+                    length: 16,
+                    byte_order: ByteOrder::BigEndian,
+                    value_type: ValueType::Unsigned,
+                    factor: 1.0,
+                    offset: 0.0,
+                },
+                value: response << 8 | u64::from(self.pid),
+            }),
+            calibration: None,
+            raw: true,
+            j1939: false,
+        }
+    }
+}
+
+struct PidRequest {
+    pid: u8,
+    interval: Duration,
+    next_due: Instant,
+}
+
+/// Sends mode 01 requests at each PID's rate, one request at a time.
+pub struct ObdPoller {
+    request_id: u32,
+    requests: Vec<PidRequest>,
+    pending: Option<(u8, Instant)>,
+}
+
+impl ObdPoller {
+    pub fn new(request_id: u32) -> ObdPoller {
+        ObdPoller {
+            request_id,
+            requests: Vec::new(),
+            pending: None,
+        }
+    }
+
+    pub fn contains(&self, pid: u8) -> bool {
+        self.requests.iter().any(|r| r.pid == pid)
+    }
+
+    /// Polls `pid` `rate` times per second.
+    pub fn add(&mut self, pid: u8, rate: f32) {
+        self.requests.push(PidRequest {
+            pid,
+            interval: Duration::from_secs_f32(1.0 / rate.max(0.01)),
+            next_due: Instant::now(),
+        });
+    }
+
+    /// The next request to send, if one is due and no other is awaiting its response.
+    pub fn poll(&mut self, now: Instant) -> Option<Frame> {
+        if let Some((_, sent)) = self.pending {
+            if now.duration_since(sent) < RESPONSE_TIMEOUT {
+                return None;
+            }
+        }
+
+        let request = self
+            .requests
+            .iter_mut()
+            .filter(|r| r.next_due <= now)
+            .min_by_key(|r| r.next_due)?;
+        request.next_due = now + request.interval;
+        self.pending = Some((request.pid, now));
+
+        Some(Frame {
+            id: self.request_id,
+            extended: false,
+            data: vec![0x02, SHOW_CURRENT_DATA, request.pid, 0x55, 0x55, 0x55, 0x55, 0x55],
+        })
+    }
+
+    /// Lets the next request go out as soon as the pending one is answered.
+    pub fn handle(&mut self, frame: &Frame) {
+        if let Some((pid, _)) = self.pending {
+            let is_response = !frame.extended
+                && frame.id >= FIRST_RESPONSE_ID
+                && frame.id <= LAST_RESPONSE_ID
+                && frame.data.get(1) == Some(&(SHOW_CURRENT_DATA + RESPONSE_OFFSET))
+                && frame.data.get(2) == Some(&pid);
+            if is_response {
+                self.pending = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Conversion;
+
+    #[test]
+    fn parses_channel_names() {
+        assert_eq!(parse_channel("obd:0x0C"), Some(0x0C));
+        assert_eq!(parse_channel("obd:12"), Some(12));
+        assert_eq!(parse_channel("Engine.RPM"), None);
+    }
+
+    #[test]
+    fn pid_units_convert_to_gauge_labels() {
+        for pid in PIDS.iter() {
+            let label = pid.unit.to_uppercase();
+            assert!(Conversion::between(pid.unit, &label).is_some(), "{} to {}", pid.unit, label);
+        }
+        let speed = find(0x0D).unwrap();
+        let to_mph = Conversion::between(speed.unit, "mph").unwrap();
+        assert!((to_mph.apply(100.0) - 62.137).abs() < 1e-3);
+    }
+
+    #[test]
+    fn decodes_responses() {
+        let engine_speed = find(0x0C).unwrap().decoder();
+        let response = Frame {
+            id: 0x7E8,
+            extended: false,
+            data: vec![0x04, 0x41, 0x0C, 0x1A, 0xF8, 0x55, 0x55, 0x55],
+        };
+        assert_eq!(engine_speed.decode(&response), Some(Ok(1726.0)));
+    }
+}
@@ -0,0 +1,190 @@
+use crate::can::{Frame, FrameFilter};
+use crate::config::Record;
+use crate::source::candump;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Writes received frames to candump log files, starting a new file once the
+/// current one grows too large or too old.
+pub struct Recorder {
+    directory: PathBuf,
+    interface: String,
+    filters: Option<Vec<FrameFilter>>,
+    max_size: Option<u64>,
+    max_duration: Option<Duration>,
+
+    file: Option<BufWriter<File>>,
+    size: u64,
+    opened: Instant,
+    files: u32,
+}
+
+impl Recorder {
+    pub fn new(config: &Record, interface: &str) -> io::Result<Recorder> {
+        let directory = PathBuf::from(config.directory.as_deref().unwrap_or("."));
+        fs::create_dir_all(&directory)?;
+        let mut recorder = Recorder {
+            directory,
+            interface: interface.to_string(),
+            filters: config
+                .frame_ids
+                .as_ref()
+                .map(|ids| {
+                    ids.iter()
+                        .map(|id| FrameFilter::exact(*id, config.extended.unwrap_or(*id > 0x7FF)))
+                        .collect()
+                }),
+            max_size: config.max_size,
+            max_duration: config.max_duration.map(|seconds| Duration::from_secs_f32(seconds.max(1.0))),
+
+            file: None,
+            size: 0,
+            opened: Instant::now(),
+            files: 0,
+        };
+        recorder.rotate()?;
+        Ok(recorder)
+    }
+
+    pub fn record(&mut self, frame: &Frame) -> io::Result<()> {
+        let recorded = self
+            .filters
+            .as_ref()
+            .is_none_or(|filters| filters.iter().any(|filter| filter.matches(frame)));
+        if !recorded {
+            return Ok(());
+        }
+
+        let full = self.max_size.is_some_and(|max_size| self.size >= max_size);
+        let expired = self.max_duration.is_some_and(|max_duration| self.opened.elapsed() >= max_duration);
+        if full || expired {
+            self.rotate()?;
+        }
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let line = candump::format_line(timestamp, &self.interface, frame);
+        if let Some(file) = self.file.as_mut() {
+            file.write_all(line.as_bytes())?;
+        }
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Writes out what is buffered, so little is lost if the power is cut.
+    pub fn flush(&mut self) -> io::Result<()> {
+        match self.file.as_mut() {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if let Some(mut file) = self.file.take() {
+            file.flush()?;
+        }
+        let started = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        // Numbered too, as rotating by size can start several files within a second.
+        let path = self
+            .directory
+            .join(format!("m8r-{}-{}.log", started.as_secs(), self.files));
+        self.file = Some(BufWriter::new(File::create(path)?));
+        self.size = 0;
+        self.opened = Instant::now();
+        self.files += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_configured_frames_and_flushes() {
+        let directory = std::env::temp_dir().join(format!("m8r-recorder-{}", std::process::id()));
+        let config = Record {
+            directory: Some(directory.to_string_lossy().into_owned()),
+            frame_ids: Some(vec![0x123]),
+            extended: None,
+            max_size: None,
+            max_duration: None,
+        };
+        let mut recorder = Recorder::new(&config, "can0").unwrap();
+        let frame = |id, extended| Frame {
+            id,
+            extended,
+            data: vec![0x01],
+        };
+        recorder.record(&frame(0x123, false)).unwrap();
+        recorder.record(&frame(0x123, true)).unwrap();
+        recorder.record(&frame(0x124, false)).unwrap();
+        recorder.flush().unwrap();
+
+        let files: Vec<PathBuf> = fs::read_dir(&directory).unwrap().map(|e| e.unwrap().path()).collect();
+        assert_eq!(files.len(), 1);
+        let content = fs::read_to_string(&files[0]).unwrap();
+        assert_eq!(content.lines().count(), 1);
+        assert!(content.ends_with(" can0 123#01\n"), "{}", content);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn records_low_extended_ids() {
+        let directory = std::env::temp_dir().join(format!("m8r-recorder-extended-{}", std::process::id()));
+        let config = Record {
+            directory: Some(directory.to_string_lossy().into_owned()),
+            frame_ids: Some(vec![0x100]),
+            extended: Some(true),
+            max_size: None,
+            max_duration: None,
+        };
+        let mut recorder = Recorder::new(&config, "can0").unwrap();
+        let frame = |extended| Frame {
+            id: 0x100,
+            extended,
+            data: vec![0x02],
+        };
+        recorder.record(&frame(false)).unwrap();
+        recorder.record(&frame(true)).unwrap();
+        recorder.flush().unwrap();
+
+        let files: Vec<PathBuf> = fs::read_dir(&directory).unwrap().map(|e| e.unwrap().path()).collect();
+        let content = fs::read_to_string(&files[0]).unwrap();
+        assert!(content.ends_with(" can0 00000100#02\n"), "{}", content);
+        assert_eq!(content.lines().count(), 1);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn starts_new_files_once_full() {
+        let directory = std::env::temp_dir().join(format!("m8r-recorder-rotate-{}", std::process::id()));
+        let config = Record {
+            directory: Some(directory.to_string_lossy().into_owned()),
+            frame_ids: None,
+            extended: None,
+            max_size: Some(40),
+            max_duration: None,
+        };
+        let mut recorder = Recorder::new(&config, "can0").unwrap();
+        let frame = Frame {
+            id: 0x123,
+            extended: false,
+            data: vec![0x01],
+        };
+        // Each line is about 32 bytes, so every second frame starts a new file.
+        for _ in 0..6 {
+            recorder.record(&frame).unwrap();
+        }
+        recorder.flush().unwrap();
+
+        let mut files: Vec<PathBuf> = fs::read_dir(&directory).unwrap().map(|e| e.unwrap().path()).collect();
+        files.sort();
+        assert_eq!(files.len(), 3);
+        for file in files.iter() {
+            assert_eq!(fs::read_to_string(file).unwrap().lines().count(), 2);
+        }
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
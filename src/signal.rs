@@ -0,0 +1,371 @@
+use half::f16;
+use serde::Deserialize;
+use std::fmt;
+
+/// The largest payload, which is a reassembled ISO-TP message rather than a CAN FD frame.
+const MAX_PAYLOAD: usize = crate::isotp::MAX_MESSAGE;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum ByteOrder {
+    BigEndian,    // Motorola
+    LittleEndian, // Intel
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ValueType {
+    Unsigned,
+    Signed,
+    Float,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DecodeError {
+    /// The frame's DLC is too short for the signal.
+    ShortFrame { length: usize, required: usize },
+    /// The signal lies past the end of the largest possible payload.
+    OutOfRange { required: usize },
+    /// The bit length can't be decoded as the value type.
+    BadType { value_type: ValueType, length: u16 },
+    /// The frame failed its alive counter or checksum check.
+    Integrity,
+    /// The sender flags the value as erroneous.
+    SenderError,
+    /// The sender has no value to report.
+    NotAvailable,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::ShortFrame { length, required } => {
+                write!(f, "frame has {} bytes, signal needs {}", length, required)
+            }
+            DecodeError::OutOfRange { required } => write!(
+                f,
+                "signal needs {} bytes, payloads carry at most {}",
+                required, MAX_PAYLOAD
+            ),
+            DecodeError::BadType { value_type, length } => {
+                write!(f, "can't decode {} bits as {:?}", length, value_type)
+            }
+            DecodeError::Integrity => write!(f, "frame failed its counter or checksum check"),
+            DecodeError::SenderError => write!(f, "sender reports an error"),
+            DecodeError::NotAvailable => write!(f, "sender reports the value as not available"),
+        }
+    }
+}
+
+/// Describes where a value lives in a frame payload, and how to turn the raw
+/// bits into an engineering value (`raw * factor + offset`).
+#[derive(Clone, Debug)]
+pub struct Signal {
+    /// Bit position of the LSB for little endian signals, or of the MSB for
+    /// big endian signals, using the DBC bit numbering.
+    pub start_bit: u16,
+    pub length: u16,
+    pub byte_order: ByteOrder,
+    pub value_type: ValueType,
+    pub factor: f32,
+    pub offset: f32,
+}
+
+impl Signal {
+    /// The number of payload bytes needed to hold the signal.
+    pub fn bytes_required(&self) -> usize {
+        let start = usize::from(self.start_bit);
+        let length = usize::from(self.length).max(1);
+
+        match self.byte_order {
+            ByteOrder::LittleEndian => (start + length - 1) / 8 + 1,
+            ByteOrder::BigEndian => {
+                // Bits run from the MSB down through the start byte, then on into the next bytes.
+                let in_first_byte = start % 8 + 1;
+                let remaining = length.saturating_sub(in_first_byte);
+                start / 8 + remaining.div_ceil(8) + 1
+            }
+        }
+    }
+
+    pub fn raw(&self, data: &[u8]) -> Result<u64, DecodeError> {
+        if self.length == 0 || self.length > 64 {
+            return Err(DecodeError::BadType {
+                value_type: self.value_type,
+                length: self.length,
+            });
+        }
+
+        let required = self.bytes_required();
+        if required > MAX_PAYLOAD {
+            return Err(DecodeError::OutOfRange { required });
+        }
+        if required > data.len() {
+            return Err(DecodeError::ShortFrame {
+                length: data.len(),
+                required,
+            });
+        }
+
+        let mut raw: u64 = 0;
+
+        match self.byte_order {
+            ByteOrder::LittleEndian => {
+                for i in (0..self.length).rev() {
+                    raw = (raw << 1) | bit(data, self.start_bit + i);
+                }
+            }
+            ByteOrder::BigEndian => {
+                let mut position = self.start_bit;
+                for _ in 0..self.length {
+                    raw = (raw << 1) | bit(data, position);
+                    if position.is_multiple_of(8) {
+                        position += 15;
+                    } else {
+                        position -= 1;
+                    }
+                }
+            }
+        }
+
+        Ok(raw)
+    }
+
+    pub fn value(&self, data: &[u8]) -> Result<f32, DecodeError> {
+        let raw = self.raw(data)?;
+
+        let value: f64 = match self.value_type {
+            ValueType::Unsigned => raw as f64,
+            ValueType::Signed => {
+                let shift = 64 - u32::from(self.length);
+                ((raw << shift) as i64 >> shift) as f64
+            }
+            ValueType::Float => match self.length {
+                16 => f16::from_bits(raw as u16).into(),
+                32 => f32::from_bits(raw as u32).into(),
+                64 => f64::from_bits(raw),
+                length => {
+                    return Err(DecodeError::BadType {
+                        value_type: self.value_type,
+                        length,
+                    })
+                }
+            },
+        };
+
+        Ok(value as f32 * self.factor + self.offset)
+    }
+
+    /// Writes the raw bits into the payload, which must be long enough to hold them.
+    pub fn encode_raw(&self, raw: u64, data: &mut [u8]) -> Result<(), DecodeError> {
+        if self.length == 0 || self.length > 64 {
+            return Err(DecodeError::BadType {
+                value_type: self.value_type,
+                length: self.length,
+            });
+        }
+        let required = self.bytes_required();
+        if required > data.len() {
+            return Err(DecodeError::ShortFrame {
+                length: data.len(),
+                required,
+            });
+        }
+
+        match self.byte_order {
+            ByteOrder::LittleEndian => {
+                for i in 0..self.length {
+                    set_bit(data, self.start_bit + i, (raw >> i) & 1);
+                }
+            }
+            ByteOrder::BigEndian => {
+                let mut position = self.start_bit;
+                for i in (0..self.length).rev() {
+                    set_bit(data, position, (raw >> i) & 1);
+                    if position.is_multiple_of(8) {
+                        position += 15;
+                    } else {
+                        position -= 1;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The reverse of `value`. Values outside what the signal can hold are clamped.
+    pub fn encode(&self, value: f32, data: &mut [u8]) -> Result<(), DecodeError> {
+        let value = f64::from((value - self.offset) / self.factor);
+        let length = u32::from(self.length.clamp(1, 64));
+        let max = u64::MAX >> (64 - length);
+
+        let raw = match self.value_type {
+            ValueType::Unsigned => value.round().clamp(0.0, max as f64) as u64,
+            ValueType::Signed => {
+                let limit = (max >> 1) as f64;
+                (value.round().clamp(-limit - 1.0, limit) as i64 as u64) & max
+            }
+            ValueType::Float => match self.length {
+                16 => u64::from(f16::from_f64(value).to_bits()),
+                32 => u64::from((value as f32).to_bits()),
+                64 => value.to_bits(),
+                length => {
+                    return Err(DecodeError::BadType {
+                        value_type: self.value_type,
+                        length,
+                    })
+                }
+            },
+        };
+
+        self.encode_raw(raw, data)
+    }
+}
+
+fn bit(data: &[u8], position: u16) -> u64 {
+    let byte = data[usize::from(position / 8)];
+    u64::from((byte >> (position % 8)) & 1)
+}
+
+fn set_bit(data: &mut [u8], position: u16, value: u64) {
+    let byte = &mut data[usize::from(position / 8)];
+    let mask = 1 << (position % 8);
+    if value == 0 {
+        *byte &= !mask;
+    } else {
+        *byte |= mask;
+    }
+}
+
+/// Selects which group of signals a multiplexed frame carries.
+#[derive(Clone, Debug)]
+pub struct Multiplexer {
+    pub signal: Signal,
+    pub value: u64,
+}
+
+impl Multiplexer {
+    pub fn matches(&self, data: &[u8]) -> bool {
+        self.signal.raw(data) == Ok(self.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signal(start_bit: u16, length: u16, byte_order: ByteOrder, value_type: ValueType) -> Signal {
+        Signal {
+            start_bit,
+            length,
+            byte_order,
+            value_type,
+            factor: 1.0,
+            offset: 0.0,
+        }
+    }
+
+    #[test]
+    fn decodes_little_endian() {
+        let signal = signal(4, 12, ByteOrder::LittleEndian, ValueType::Unsigned);
+        assert_eq!(signal.bytes_required(), 2);
+        assert_eq!(signal.raw(&[0xA0, 0xBC]), Ok(0xBCA));
+    }
+
+    #[test]
+    fn decodes_big_endian() {
+        let word = signal(7, 16, ByteOrder::BigEndian, ValueType::Unsigned);
+        assert_eq!(word.raw(&[0x12, 0x34]), Ok(0x1234));
+
+        // From bit 3 of the first byte down, then on through the second byte.
+        let unaligned = signal(3, 12, ByteOrder::BigEndian, ValueType::Unsigned);
+        assert_eq!(unaligned.bytes_required(), 2);
+        assert_eq!(unaligned.raw(&[0xFA, 0xBC]), Ok(0xABC));
+    }
+
+    #[test]
+    fn decodes_single_bits() {
+        let bit = signal(9, 1, ByteOrder::LittleEndian, ValueType::Unsigned);
+        assert_eq!(bit.raw(&[0x00, 0x02]), Ok(1));
+        assert_eq!(bit.raw(&[0xFF, 0xFD]), Ok(0));
+    }
+
+    #[test]
+    fn decodes_signed_values() {
+        let signed = signal(0, 12, ByteOrder::LittleEndian, ValueType::Signed);
+        assert_eq!(signed.value(&[0xFF, 0x0F]), Ok(-1.0));
+        assert_eq!(signed.value(&[0xFF, 0x07]), Ok(2047.0));
+        assert_eq!(signed.value(&[0x00, 0x08]), Ok(-2048.0));
+    }
+
+    #[test]
+    fn decodes_floats() {
+        let half = signal(7, 16, ByteOrder::BigEndian, ValueType::Float);
+        assert_eq!(half.value(&[0x3E, 0x00]), Ok(1.5));
+
+        let single = signal(0, 32, ByteOrder::LittleEndian, ValueType::Float);
+        assert_eq!(single.value(&(-2.25f32).to_le_bytes()), Ok(-2.25));
+
+        let odd = signal(0, 24, ByteOrder::LittleEndian, ValueType::Float);
+        assert_eq!(
+            odd.value(&[0; 3]),
+            Err(DecodeError::BadType {
+                value_type: ValueType::Float,
+                length: 24
+            })
+        );
+    }
+
+    #[test]
+    fn applies_factor_and_offset() {
+        let mut scaled = signal(0, 8, ByteOrder::LittleEndian, ValueType::Unsigned);
+        scaled.factor = 0.5;
+        scaled.offset = -40.0;
+        assert_eq!(scaled.value(&[100]), Ok(10.0));
+    }
+
+    #[test]
+    fn rejects_short_frames_and_bad_lengths() {
+        let word = signal(8, 16, ByteOrder::LittleEndian, ValueType::Unsigned);
+        assert_eq!(word.raw(&[0, 0]), Err(DecodeError::ShortFrame { length: 2, required: 3 }));
+
+        let empty = signal(0, 0, ByteOrder::LittleEndian, ValueType::Unsigned);
+        assert!(matches!(empty.raw(&[0]), Err(DecodeError::BadType { .. })));
+
+        let beyond = signal(8 * MAX_PAYLOAD as u16, 8, ByteOrder::LittleEndian, ValueType::Unsigned);
+        assert!(matches!(beyond.raw(&[0]), Err(DecodeError::OutOfRange { .. })));
+    }
+
+    #[test]
+    fn encodes_what_it_decodes() {
+        let signals = [
+            (signal(3, 12, ByteOrder::BigEndian, ValueType::Signed), -300.0),
+            (signal(3, 13, ByteOrder::LittleEndian, ValueType::Unsigned), 5000.0),
+            (signal(23, 16, ByteOrder::BigEndian, ValueType::Float), 1.5),
+            (signal(0, 64, ByteOrder::LittleEndian, ValueType::Signed), -7.0),
+        ];
+        for (signal, value) in signals.iter() {
+            let mut data = vec![0xAA; 8];
+            signal.encode(*value, &mut data).unwrap();
+            assert_eq!(signal.value(&data), Ok(*value), "{:?}", signal);
+        }
+    }
+
+    #[test]
+    fn encoding_clamps_and_keeps_other_bits() {
+        let byte = signal(15, 8, ByteOrder::BigEndian, ValueType::Unsigned);
+        let mut data = vec![0xAA, 0x00, 0xAA];
+        byte.encode(999.0, &mut data).unwrap();
+        assert_eq!(data, vec![0xAA, 0xFF, 0xAA]);
+    }
+
+    #[test]
+    fn matches_multiplexer_value() {
+        let multiplexer = Multiplexer {
+            signal: signal(0, 8, ByteOrder::LittleEndian, ValueType::Unsigned),
+            value: 2,
+        };
+        assert!(multiplexer.matches(&[2, 0]));
+        assert!(!multiplexer.matches(&[3, 0]));
+        assert!(!multiplexer.matches(&[]));
+    }
+}
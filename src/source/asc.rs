@@ -0,0 +1,134 @@
+use super::since_first;
+use crate::can::Frame;
+use std::fs;
+use std::io;
+use std::time::Duration;
+
+/// Reads a Vector ASCII log. Lines other than CAN and CAN FD data frames, such as
+/// error frames and other events, are skipped.
+pub fn read(path: &str) -> io::Result<Vec<(Duration, Frame)>> {
+    let content = fs::read_to_string(path)?;
+    Ok(since_first(parse(&content)))
+}
+
+fn parse(content: &str) -> Vec<(f64, Frame)> {
+    let mut hex = true;
+    let mut relative = false;
+    let mut previous = 0.0;
+    let mut frames = Vec::new();
+    for line in content.lines() {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.as_slice() {
+            ["base", base, "timestamps", timestamps, ..] => {
+                hex = *base == "hex";
+                relative = *timestamps == "relative";
+            }
+            _ => {
+                if let Some((timestamp, frame)) = parse_frame(&tokens, hex) {
+                    // Relative timestamps count from the previous frame.
+                    let timestamp = if relative { previous + timestamp } else { timestamp };
+                    previous = timestamp;
+                    frames.push((timestamp, frame));
+                }
+            }
+        }
+    }
+    frames
+}
+
+/// Parses `0.010000 1 18FEF100x Rx d 8 01 02 03 04 05 06 07 08`, or
+/// `0.010000 CANFD 1 Rx 123 [name] 1 0 d 12 01 02 ...` for an FD frame.
+fn parse_frame(tokens: &[&str], hex: bool) -> Option<(f64, Frame)> {
+    let timestamp = tokens.first()?.parse::<f64>().ok()?;
+    let (id, data) = if *tokens.get(1)? == "CANFD" {
+        // A symbolic name may follow the ID, before the BRS and ESI flags.
+        let flags = if matches!(*tokens.get(5)?, "0" | "1") { 5 } else { 6 };
+        let length = tokens.get(flags + 3)?.parse::<usize>().ok()?;
+        (tokens.get(4)?, tokens.get(flags + 4..flags + 4 + length)?)
+    } else {
+        if !matches!(*tokens.get(3)?, "Rx" | "Tx") || *tokens.get(4)? != "d" {
+            return None;
+        }
+        let length = usize::from_str_radix(tokens.get(5)?, 16).ok()?.min(8);
+        (tokens.get(2)?, tokens.get(6..6 + length)?)
+    };
+
+    let (id, extended) = match id.strip_suffix('x') {
+        Some(id) => (id, true),
+        None => (*id, false),
+    };
+    let radix = if hex { 16 } else { 10 };
+    let id = u32::from_str_radix(id, radix).ok()?;
+    let data = data
+        .iter()
+        .map(|byte| u8::from_str_radix(byte, radix).ok())
+        .collect::<Option<Vec<u8>>>()?;
+
+    Some((timestamp, Frame { id, extended, data }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(content: &str) -> Vec<(f64, u32, bool, Vec<u8>)> {
+        parse(content)
+            .into_iter()
+            .map(|(timestamp, frame)| (timestamp, frame.id, frame.extended, frame.data))
+            .collect()
+    }
+
+    #[test]
+    fn parses_classic_frames() {
+        let log = "date Mon Jan 1 10:00:00.000 am 2024\n\
+                   base hex  timestamps absolute\n\
+                   0.010000 1 18FEF100x Rx d 3 01 02 0A\n\
+                   0.020000 1 7E8 Tx d 2 41 0C Length = 0 BitCount = 0\n";
+        assert_eq!(
+            frames(log),
+            vec![
+                (0.01, 0x18FE_F100, true, vec![0x01, 0x02, 0x0A]),
+                (0.02, 0x7E8, false, vec![0x41, 0x0C]),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_fd_frames_with_and_without_names() {
+        let log = "base hex  timestamps absolute\n\
+                   0.010000 CANFD 1 Rx 123 1 0 8 8 00 01 02 03 04 05 06 07\n\
+                   0.020000 CANFD 1 Rx 456x EngineData 1 0 9 12 00 01 02 03 04 05 06 07 08 09 0A 0B\n";
+        let frames = frames(log);
+        assert_eq!(frames[0], (0.01, 0x123, false, (0..8).collect()));
+        assert_eq!(frames[1], (0.02, 0x456, true, (0..12).collect()));
+    }
+
+    #[test]
+    fn reads_decimal_bases() {
+        let log = "base dec  timestamps absolute\n\
+                   1.000000 1 291 Rx d 2 10 255\n";
+        assert_eq!(frames(log), vec![(1.0, 291, false, vec![10, 255])]);
+    }
+
+    #[test]
+    fn adds_up_relative_timestamps() {
+        let log = "base hex  timestamps relative\n\
+                   0.500000 1 100 Rx d 1 00\n\
+                   0.250000 1 100 Rx d 1 01\n";
+        let timestamps: Vec<f64> = frames(log).iter().map(|f| f.0).collect();
+        assert_eq!(timestamps, vec![0.5, 0.75]);
+    }
+
+    #[test]
+    fn skips_errors_and_events() {
+        let log = "base hex  timestamps absolute\n\
+                   Begin Triggerblock Mon Jan 1 10:00:00.000 am 2024\n\
+                   0.000000 Start of measurement\n\
+                   0.010000 1 ErrorFrame\n\
+                   0.020000 1 123 Rx r\n\
+                   0.030000 CAN 1 Status:chip status error active\n\
+                   0.040000 1 123 Rx d 1 AA\n\
+                   End TriggerBlock\n";
+        assert_eq!(frames(log), vec![(0.04, 0x123, false, vec![0xAA])]);
+    }
+}
@@ -0,0 +1,121 @@
+use super::{invalid_line, since_first};
+use crate::can::Frame;
+use std::fs;
+use std::io;
+use std::time::Duration;
+
+/// Reads a log written by `candump -l`, with each frame's time since the first.
+pub fn read(path: &str) -> io::Result<Vec<(Duration, Frame)>> {
+    let content = fs::read_to_string(path)?;
+    let mut frames = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (timestamp, frame) = parse_line(line).ok_or_else(|| invalid_line(path, number, line))?;
+        // Remote and error frames carry no values.
+        if let Some(frame) = frame {
+            frames.push((timestamp, frame));
+        }
+    }
+    Ok(since_first(frames))
+}
+
+/// Parses `(1436509052.249713) can0 123#DEADBEEF`, or `123##1DEADBEEF` for an FD frame.
+fn parse_line(line: &str) -> Option<(f64, Option<Frame>)> {
+    let mut parts = line.split_whitespace();
+    let timestamp = parts
+        .next()?
+        .strip_prefix('(')?
+        .strip_suffix(')')?
+        .parse::<f64>()
+        .ok()?;
+    let _interface = parts.next()?;
+    let (id, data) = parts.next()?.split_once('#')?;
+
+    let extended = id.len() == 8;
+    let id = u32::from_str_radix(id, 16).ok()?;
+    if id & libc::CAN_ERR_FLAG != 0 || data.starts_with('R') {
+        return Some((timestamp, None));
+    }
+    let data = match data.strip_prefix('#') {
+        // The first digit holds the FD flags.
+        Some(fd_data) => fd_data.get(1..)?,
+        None => data,
+    };
+    if data.len() % 2 != 0 {
+        return None;
+    }
+    let data = (0..data.len())
+        .step_by(2)
+        // `get` rather than indexing, as a non-ASCII character may split a pair.
+        .map(|i| u8::from_str_radix(data.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+
+    Some((timestamp, Some(Frame { id, extended, data })))
+}
+
+/// Formats a frame as `candump -l` does, with its time in seconds since the epoch.
+pub fn format_line(timestamp: Duration, interface: &str, frame: &Frame) -> String {
+    let id = if frame.extended {
+        format!("{:08X}", frame.id)
+    } else {
+        format!("{:03X}", frame.id)
+    };
+    // Frames with more than 8 bytes can only have been FD frames, flags are not kept.
+    let separator = if frame.data.len() > 8 { "##0" } else { "#" };
+    let data: String = frame.data.iter().map(|byte| format!("{:02X}", byte)).collect();
+    format!(
+        "({}.{:06}) {} {}{}{}\n",
+        timestamp.as_secs(),
+        timestamp.subsec_micros(),
+        interface,
+        id,
+        separator,
+        data
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_frames() {
+        let (timestamp, frame) = parse_line("(1436509052.249713) vcan0 123#DEADBEEF").unwrap();
+        let frame = frame.unwrap();
+        assert_eq!(timestamp, 1436509052.249713);
+        assert_eq!((frame.id, frame.extended, frame.data), (0x123, false, vec![0xDE, 0xAD, 0xBE, 0xEF]));
+
+        let frame = parse_line("(1.0) can0 18FEF100##1000102030405060708").unwrap().1.unwrap();
+        assert!(frame.extended);
+        assert_eq!(frame.data, (0..9).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn skips_remote_and_error_frames() {
+        assert!(parse_line("(1.0) can0 7DF#R").unwrap().1.is_none());
+        assert!(parse_line("(1.0) can0 20000080#0000000000000000").unwrap().1.is_none());
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        assert!(parse_line("(1.0) can0 123#ABC").is_none());
+        assert!(parse_line("(1.0) can0 123#a\u{e9}b").is_none());
+        assert!(parse_line("can0 123#00").is_none());
+    }
+
+    #[test]
+    fn formats_what_it_parses() {
+        let frame = Frame {
+            id: 0x18FE_F100,
+            extended: true,
+            data: vec![0x01, 0xAB],
+        };
+        let line = format_line(Duration::from_micros(1_500_000), "can0", &frame);
+        assert_eq!(line, "(1.500000) can0 18FEF100#01AB\n");
+        let parsed = parse_line(line.trim()).unwrap().1.unwrap();
+        assert_eq!((parsed.id, parsed.extended, parsed.data), (frame.id, frame.extended, frame.data));
+    }
+}
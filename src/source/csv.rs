@@ -0,0 +1,123 @@
+use super::{invalid_line, since_first};
+use crate::can::Frame;
+use crate::config::CsvLayout;
+use std::fs;
+use std::io;
+use std::time::Duration;
+
+/// Reads a CSV trace, with the columns given by the layout.
+pub fn read(path: &str, layout: &CsvLayout) -> io::Result<Vec<(Duration, Frame)>> {
+    let content = fs::read_to_string(path)?;
+    let mut frames = Vec::new();
+    let lines = content.lines().enumerate().skip(layout.header_lines.unwrap_or(1));
+    for (number, line) in lines {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let frame = parse_line(line, layout).ok_or_else(|| invalid_line(path, number, line))?;
+        frames.push(frame);
+    }
+    Ok(since_first(frames))
+}
+
+fn parse_line(line: &str, layout: &CsvLayout) -> Option<(f64, Frame)> {
+    let fields: Vec<&str> = line
+        .split(layout.delimiter.unwrap_or(','))
+        .map(|field| field.trim().trim_matches('"').trim())
+        .collect();
+    let field = |column: usize| fields.get(column.checked_sub(1)?).copied();
+
+    let timestamp = field(layout.timestamp)?.parse::<f64>().ok()? * layout.timestamp_scale.unwrap_or(1.0);
+
+    let id = field(layout.id)?;
+    let id = id.strip_prefix("0x").or_else(|| id.strip_prefix("0X")).unwrap_or(id);
+    let (id, marked_extended) = match id.strip_suffix(|c| c == 'x' || c == 'X') {
+        Some(id) => (id, true),
+        None => (id, false),
+    };
+    let id = u32::from_str_radix(id, 16).ok()?;
+    let extended = marked_extended || id > 0x7FF;
+
+    let length = match layout.dlc {
+        Some(dlc) => Some(field(dlc)?.parse::<usize>().ok()?),
+        None => None,
+    };
+    // With a DLC, the columns after the data bytes can hold anything else.
+    let mut data = Vec::new();
+    for field in fields.get(layout.data.checked_sub(1)?..)? {
+        if length.is_some_and(|length| data.len() >= length) {
+            break;
+        }
+        for byte in field.split_whitespace() {
+            data.push(u8::from_str_radix(byte.trim_start_matches("0x"), 16).ok()?);
+        }
+    }
+    if let Some(length) = length {
+        data = data.get(..length)?.to_vec();
+    }
+
+    Some((timestamp, Frame { id, extended, data }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(dlc: Option<usize>, data: usize) -> CsvLayout {
+        CsvLayout {
+            timestamp: 1,
+            id: 2,
+            dlc,
+            data,
+            delimiter: None,
+            header_lines: None,
+            timestamp_scale: None,
+        }
+    }
+
+    #[test]
+    fn parses_bytes_in_one_column() {
+        let (timestamp, frame) = parse_line("0.25,0x123,DE AD BE EF", &layout(None, 3)).unwrap();
+        assert_eq!(timestamp, 0.25);
+        assert_eq!((frame.id, frame.extended, frame.data), (0x123, false, vec![0xDE, 0xAD, 0xBE, 0xEF]));
+    }
+
+    #[test]
+    fn parses_one_byte_per_column() {
+        let frame = parse_line("1.5,\"7E8\",3,0x41,0x0C,1A", &layout(Some(3), 4)).unwrap().1;
+        assert_eq!(frame.data, vec![0x41, 0x0C, 0x1A]);
+    }
+
+    #[test]
+    fn marks_extended_ids() {
+        let frame = parse_line("0,100x,00", &layout(None, 3)).unwrap().1;
+        assert_eq!((frame.id, frame.extended), (0x100, true));
+        let frame = parse_line("0,18FEF100,00", &layout(None, 3)).unwrap().1;
+        assert_eq!((frame.id, frame.extended), (0x18FE_F100, true));
+    }
+
+    #[test]
+    fn truncates_to_the_dlc() {
+        let frame = parse_line("0,123,2,01 02 03 04", &layout(Some(3), 4)).unwrap().1;
+        assert_eq!(frame.data, vec![0x01, 0x02]);
+        assert!(parse_line("0,123,5,01 02 03 04", &layout(Some(3), 4)).is_none());
+    }
+
+    #[test]
+    fn ignores_columns_after_the_data() {
+        let line = "0,123,2,01,02,1,Rx,engine speed";
+        let frame = parse_line(line, &layout(Some(3), 4)).unwrap().1;
+        assert_eq!(frame.data, vec![0x01, 0x02]);
+        assert!(parse_line(line, &layout(None, 4)).is_none());
+    }
+
+    #[test]
+    fn scales_timestamps_and_splits_on_the_delimiter() {
+        let mut layout = layout(None, 3);
+        layout.delimiter = Some(';');
+        layout.timestamp_scale = Some(0.001);
+        let (timestamp, frame) = parse_line("1500;7DF;02 01 0C", &layout).unwrap();
+        assert_eq!(timestamp, 1.5);
+        assert_eq!((frame.id, frame.data), (0x7DF, vec![0x02, 0x01, 0x0C]));
+    }
+}
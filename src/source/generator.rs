@@ -0,0 +1,295 @@
+use super::FrameSource;
+use crate::can::Frame;
+use crate::config::Waveform;
+use crate::decoder::Decoder;
+use crate::j1939;
+use crate::signal::{Multiplexer, Signal, ValueType};
+use crate::units::Conversion;
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Makes up one gauge's values, in the displayed unit.
+struct Wave {
+    waveform: Waveform,
+    low: f32,
+    high: f32,
+    conversion: Conversion,
+    value: f32, // Where a random walk has got to
+}
+
+impl Wave {
+    fn value(&mut self, time: f32, random: &mut u32) -> f32 {
+        let range = self.high - self.low;
+        let phase = |period: Option<f32>| (time / period.unwrap_or(5.0).max(0.01)).fract();
+        match &self.waveform {
+            // Starts at the low end, like the other waveforms.
+            Waveform::Sine { period } => self.low + range * 0.5 * (1.0 - (2.0 * PI * phase(*period)).cos()),
+            Waveform::Ramp { period } => self.low + range * phase(*period),
+            Waveform::Square { period, duty } => {
+                if phase(*period) < duty.unwrap_or(0.5) {
+                    self.high
+                } else {
+                    self.low
+                }
+            }
+            Waveform::RandomWalk { step } => {
+                let step = step.unwrap_or(0.02) * range;
+                let next = self.value + step * (2.0 * next_random(random) - 1.0);
+                self.value = next.clamp(self.low.min(self.high), self.low.max(self.high));
+                self.value
+            }
+            Waveform::Steps { values, duration } => {
+                let step = (time / duration.unwrap_or(1.0).max(0.01)) as usize;
+                values.get(step % values.len().max(1)).copied().unwrap_or(self.low)
+            }
+        }
+    }
+}
+
+/// The values a decoder reads as valid, or 0 to 100 for floats.
+pub fn range(decoder: &Decoder) -> (f32, f32) {
+    let signal = &decoder.signal;
+    let length = i32::from(signal.length.clamp(1, 32));
+    let (low, high) = match signal.value_type {
+        ValueType::Unsigned if decoder.j1939 => (0.0, j1939::largest_valid(signal.length.min(32)) as f32),
+        ValueType::Unsigned => (0.0, 2f32.powi(length) - 1.0),
+        ValueType::Signed => (-(2f32.powi(length - 1)), 2f32.powi(length - 1) - 1.0),
+        ValueType::Float => return (0.0, 100.0),
+    };
+    let (low, high) = (low * signal.factor + signal.offset, high * signal.factor + signal.offset);
+    (low.min(high), low.max(high))
+}
+
+/// The gauges sharing a frame, and its multiplexer value if it is multiplexed.
+struct Message {
+    id: u32,
+    extended: bool,
+    multiplexer: Option<Multiplexer>,
+    signals: Vec<(Signal, Wave)>,
+}
+
+/// Sends made up values, encoded into the frames the gauges decode. Calibration
+/// curves aren't inverted, so calibrated gauges see the values before calibration.
+pub struct Generator {
+    messages: Vec<Message>,
+    interval: Duration,
+    start: Instant,
+    next: Instant,
+    pending: VecDeque<Frame>,
+    random: u32,
+}
+
+impl Generator {
+    pub fn new(rate: f32) -> Generator {
+        let now = Instant::now();
+        Generator {
+            messages: Vec::new(),
+            interval: Duration::from_secs_f32(1.0 / rate.max(0.01)),
+            start: now,
+            next: now,
+            pending: VecDeque::new(),
+            random: 0x2545_F491,
+        }
+    }
+
+    /// Generates values between `low` and `high`, in the displayed unit, for the decoder.
+    pub fn add(&mut self, decoder: &Decoder, waveform: Waveform, low: f32, high: f32, conversion: Conversion) {
+        let wave = Wave {
+            waveform,
+            low,
+            high,
+            conversion,
+            value: (low + high) / 2.0,
+        };
+        let id = decoder.filter.first_id;
+        let extended = decoder.filter.extended;
+        let same_frame = |message: &&mut Message| {
+            message.id == id
+                && message.extended == extended
+                && match (&message.multiplexer, &decoder.multiplexer) {
+                    (Some(a), Some(b)) => a.value == b.value && a.signal.start_bit == b.signal.start_bit,
+                    (None, None) => true,
+                    _ => false,
+                }
+        };
+        match self.messages.iter_mut().find(same_frame) {
+            Some(message) => message.signals.push((decoder.signal.clone(), wave)),
+            None => self.messages.push(Message {
+                id,
+                extended,
+                multiplexer: decoder.multiplexer.clone(),
+                signals: vec![(decoder.signal.clone(), wave)],
+            }),
+        }
+    }
+
+    fn generate(&mut self, time: f32) {
+        for message in self.messages.iter_mut() {
+            let length = message
+                .signals
+                .iter()
+                .map(|(signal, _)| signal)
+                .chain(message.multiplexer.as_ref().map(|m| &m.signal))
+                .map(Signal::bytes_required)
+                .fold(8, usize::max);
+            let mut data = vec![0; length];
+            // Signals that can't be encoded are left out, the gauge shows their decode error.
+            if let Some(multiplexer) = &message.multiplexer {
+                let _ = multiplexer.signal.encode_raw(multiplexer.value, &mut data);
+            }
+            for (signal, wave) in message.signals.iter_mut() {
+                let value = wave.value(time, &mut self.random);
+                let _ = signal.encode(wave.conversion.invert(value), &mut data);
+            }
+            self.pending.push_back(Frame {
+                id: message.id,
+                extended: message.extended,
+                data,
+            });
+        }
+    }
+}
+
+impl FrameSource for Generator {
+    fn read_frame(&mut self, timeout: Duration) -> io::Result<Frame> {
+        if let Some(frame) = self.pending.pop_front() {
+            return Ok(frame);
+        }
+
+        let now = Instant::now();
+        if self.next > now + timeout {
+            thread::sleep(timeout);
+            return Err(io::Error::new(io::ErrorKind::WouldBlock, "No frame due"));
+        }
+        if self.next > now {
+            thread::sleep(self.next - now);
+        }
+        let time = self.next.duration_since(self.start).as_secs_f32();
+        // Skip ahead rather than catch up, if reading has fallen behind.
+        self.next = (self.next + self.interval).max(now);
+        self.generate(time);
+
+        self.pending
+            .pop_front()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::WouldBlock, "No gauges to generate"))
+    }
+
+    fn write_frame(&mut self, _frame: &Frame) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn next_random(state: &mut u32) -> f32 {
+    // xorshift32, which is plenty for wandering needles.
+    *state ^= *state << 13;
+    *state ^= *state >> 17;
+    *state ^= *state << 5;
+    *state as f32 / u32::MAX as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::can::FrameFilter;
+    use crate::signal::ByteOrder;
+
+    fn byte_decoder(byte: u16) -> Decoder {
+        Decoder {
+            filter: FrameFilter::exact(0x600, false),
+            signal: Signal {
+                start_bit: byte * 8,
+                length: 8,
+                byte_order: ByteOrder::LittleEndian,
+                value_type: ValueType::Unsigned,
+                factor: 1.0,
+                offset: 0.0,
+            },
+            multiplexer: None,
+            calibration: None,
+            raw: false,
+            j1939: false,
+        }
+    }
+
+    fn steps(value: f32) -> Waveform {
+        Waveform::Steps {
+            values: vec![value],
+            duration: None,
+        }
+    }
+
+    #[test]
+    fn encodes_signals_sharing_a_frame_into_one() {
+        let (first, second) = (byte_decoder(0), byte_decoder(3));
+        let mut generator = Generator::new(50.0);
+        generator.add(&first, steps(12.0), 0.0, 255.0, Conversion::identity());
+        generator.add(&second, steps(34.0), 0.0, 255.0, Conversion::identity());
+
+        let frame = generator.read_frame(Duration::from_millis(100)).unwrap();
+        assert_eq!((frame.id, frame.data.len()), (0x600, 8));
+        assert_eq!(first.decode(&frame), Some(Ok(12.0)));
+        assert_eq!(second.decode(&frame), Some(Ok(34.0)));
+    }
+
+    #[test]
+    fn generates_multiplexed_obd_replies() {
+        let rpm = crate::obd::find(0x0C).unwrap().decoder();
+        let speed = crate::obd::find(0x0D).unwrap().decoder();
+        let mut generator = Generator::new(50.0);
+        generator.add(&rpm, steps(3000.0), 0.0, 8000.0, Conversion::identity());
+        generator.add(&speed, steps(88.0), 0.0, 255.0, Conversion::identity());
+
+        let timeout = Duration::from_millis(100);
+        let frames = [generator.read_frame(timeout).unwrap(), generator.read_frame(timeout).unwrap()];
+        assert_eq!(frames.iter().filter_map(|f| rpm.decode(f)).collect::<Vec<_>>(), vec![Ok(3000.0)]);
+        assert_eq!(frames.iter().filter_map(|f| speed.decode(f)).collect::<Vec<_>>(), vec![Ok(88.0)]);
+    }
+
+    #[test]
+    fn generates_whole_uds_responses() {
+        let did = crate::config::UdsDid {
+            request_id: 0x7E0,
+            response_id: 0x7E8,
+            extended: false,
+            did: 0xF40C,
+            rate: None,
+            session: None,
+        };
+        let mut signal = byte_decoder(0).signal;
+        signal.start_bit = 6 * 8;
+        let decoder = crate::uds::decoder(&did, signal);
+        let mut generator = Generator::new(50.0);
+        generator.add(&decoder, steps(42.0), 0.0, 255.0, Conversion::identity());
+
+        let frame = generator.read_frame(Duration::from_millis(100)).unwrap();
+        assert_eq!(&frame.data[..3], &[0x62, 0xF4, 0x0C]);
+        assert_eq!(frame.data.len(), 10);
+        assert_eq!(decoder.decode(&frame), Some(Ok(42.0)));
+    }
+
+    #[test]
+    fn converts_back_to_the_unit_on_the_wire() {
+        let decoder = byte_decoder(0);
+        let mut generator = Generator::new(50.0);
+        let kpa_to_bar = Conversion::between("kPa", "bar").unwrap();
+        generator.add(&decoder, steps(1.5), 0.0, 2.0, kpa_to_bar);
+
+        let frame = generator.read_frame(Duration::from_millis(100)).unwrap();
+        assert_eq!(decoder.decode(&frame), Some(Ok(150.0)));
+    }
+
+    #[test]
+    fn ranges_follow_the_signal() {
+        let mut decoder = byte_decoder(0);
+        assert_eq!(range(&decoder), (0.0, 255.0));
+        decoder.j1939 = true;
+        assert_eq!(range(&decoder), (0.0, 250.0));
+        decoder.j1939 = false;
+        decoder.signal.value_type = ValueType::Signed;
+        decoder.signal.factor = -0.5;
+        assert_eq!(range(&decoder), (-63.5, 64.0));
+    }
+}
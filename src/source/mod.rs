@@ -0,0 +1,71 @@
+pub mod asc;
+pub mod candump;
+pub mod csv;
+pub mod generator;
+pub mod replay;
+pub mod slcan;
+
+use crate::can::{CanSocket, Frame};
+use crate::config::{Replay, TraceFormat};
+use std::io;
+use std::time::Duration;
+
+/// Where frames come from: a live interface, or a recording played back.
+pub trait FrameSource {
+    /// Waits up to `timeout` for the next frame.
+    fn read_frame(&mut self, timeout: Duration) -> io::Result<Frame>;
+    /// Sends a frame, e.g. a diagnostic request. Sources that can't send drop it.
+    fn write_frame(&mut self, frame: &Frame) -> io::Result<()>;
+}
+
+impl FrameSource for CanSocket {
+    fn read_frame(&mut self, timeout: Duration) -> io::Result<Frame> {
+        self.set_read_timeout(timeout)?;
+        CanSocket::read_frame(self)
+    }
+
+    fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
+        CanSocket::write_frame(self, frame)
+    }
+}
+
+/// Reads the frames of a recording to replay, in whichever format it is in.
+pub fn read_recording(config: &Replay) -> io::Result<Vec<(Duration, Frame)>> {
+    let format = config.format.unwrap_or_else(|| {
+        let file = config.file.to_lowercase();
+        if file.ends_with(".asc") {
+            TraceFormat::Asc
+        } else if file.ends_with(".csv") {
+            TraceFormat::Csv
+        } else {
+            TraceFormat::Candump
+        }
+    });
+    match format {
+        TraceFormat::Candump => candump::read(&config.file),
+        TraceFormat::Asc => asc::read(&config.file),
+        TraceFormat::Csv => {
+            let layout = config
+                .csv
+                .as_ref()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "CSV replay needs a csv layout"))?;
+            csv::read(&config.file, layout)
+        }
+    }
+}
+
+/// Makes timestamps, in seconds, relative to the first frame.
+fn since_first(frames: Vec<(f64, Frame)>) -> Vec<(Duration, Frame)> {
+    let first = frames.first().map_or(0.0, |(timestamp, _)| *timestamp);
+    frames
+        .into_iter()
+        .map(|(timestamp, frame)| (Duration::from_secs_f64((timestamp - first).max(0.0)), frame))
+        .collect()
+}
+
+fn invalid_line(path: &str, number: usize, line: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{}:{}: Invalid line {}", path, number + 1, line),
+    )
+}
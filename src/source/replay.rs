@@ -0,0 +1,57 @@
+use super::FrameSource;
+use crate::can::Frame;
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Plays back recorded frames with their original timing, scaled by `speed`.
+pub struct Replay {
+    frames: Vec<(Duration, Frame)>, // Time since the first frame
+    index: usize,
+    start: Instant,
+    speed: f32,
+    looping: bool,
+}
+
+impl Replay {
+    pub fn new(frames: Vec<(Duration, Frame)>, speed: f32, looping: bool) -> Replay {
+        Replay {
+            frames,
+            index: 0,
+            start: Instant::now(),
+            speed: speed.max(0.01),
+            looping,
+        }
+    }
+}
+
+impl FrameSource for Replay {
+    fn read_frame(&mut self, timeout: Duration) -> io::Result<Frame> {
+        if self.index == self.frames.len() {
+            if self.looping && !self.frames.is_empty() {
+                self.index = 0;
+                self.start = Instant::now();
+            } else {
+                thread::sleep(timeout);
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "End of recording"));
+            }
+        }
+
+        let (time, frame) = &self.frames[self.index];
+        let due = self.start + time.div_f32(self.speed);
+        let now = Instant::now();
+        if due > now + timeout {
+            thread::sleep(timeout);
+            return Err(io::Error::new(io::ErrorKind::WouldBlock, "No frame due"));
+        }
+        if due > now {
+            thread::sleep(due - now);
+        }
+        self.index += 1;
+        Ok(frame.clone())
+    }
+
+    fn write_frame(&mut self, _frame: &Frame) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,300 @@
+use super::FrameSource;
+use crate::can::Frame;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::mem;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Bitrates selected with the `S` command, by their index.
+const BITRATES: [u32; 9] = [10_000, 20_000, 50_000, 100_000, 125_000, 250_000, 500_000, 800_000, 1_000_000];
+
+/// A serial adapter speaking the SLCAN (Lawicel) ASCII protocol.
+pub struct Slcan {
+    port: File,
+    buffer: Vec<u8>,
+}
+
+impl Slcan {
+    /// Opens the device, sets the CAN bitrate and opens the channel.
+    pub fn open(device: &str, bitrate: u32, baud: u32) -> io::Result<Slcan> {
+        let bitrate_index = BITRATES
+            .iter()
+            .position(|b| *b == bitrate)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Unsupported bitrate {}", bitrate)))?;
+        let port = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(device)?;
+        set_raw(&port, baud)?;
+
+        let mut slcan = Slcan {
+            port,
+            buffer: Vec::new(),
+        };
+        // Close first, in case the channel was left open, as the bitrate can only change while closed.
+        slcan.command("C")?;
+        slcan.command(&format!("S{}", bitrate_index))?;
+        slcan.command("O")?;
+        slcan.buffer.clear();
+        Ok(slcan)
+    }
+
+    fn command(&mut self, command: &str) -> io::Result<()> {
+        self.port.write_all(format!("{}\r", command).as_bytes())?;
+        // The reply, a CR or a BEL for an error, isn't waited for as some adapters don't send one.
+        Ok(())
+    }
+
+    /// Waits up to `timeout` for more input, returning whether any arrived.
+    fn fill(&mut self, timeout: Duration) -> io::Result<bool> {
+        let mut poll = libc::pollfd {
+            fd: self.port.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let millis = timeout.as_millis().min(i32::MAX as u128) as libc::c_int;
+        let result = unsafe { libc::poll(&mut poll, 1, millis) };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if result == 0 {
+            return Ok(false);
+        }
+
+        let mut bytes = [0u8; 256];
+        let read = if poll.revents & libc::POLLIN != 0 {
+            self.port.read(&mut bytes)
+        } else {
+            Ok(0)
+        };
+        match read {
+            Ok(read) if read > 0 => {
+                self.buffer.extend_from_slice(&bytes[..read]);
+                Ok(true)
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => Ok(false),
+            // Once the adapter is unplugged, the port stays readable without data. Wait out
+            // the timeout rather than have the caller spin on it.
+            _ => {
+                thread::sleep(timeout);
+                Err(io::Error::new(io::ErrorKind::BrokenPipe, "SLCAN adapter hung up"))
+            }
+        }
+    }
+}
+
+impl FrameSource for Slcan {
+    fn read_frame(&mut self, timeout: Duration) -> io::Result<Frame> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            // Replies to commands end in a CR too, and errors are a lone BEL.
+            while let Some(end) = self.buffer.iter().position(|b| *b == b'\r' || *b == 0x07) {
+                let line: Vec<u8> = self.buffer.drain(..=end).collect();
+                if let Some(frame) = parse_line(&line[..end]) {
+                    return Ok(frame);
+                }
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() || !self.fill(remaining)? {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "No frame received"));
+            }
+        }
+    }
+
+    fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
+        if frame.data.len() > 8 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "SLCAN can't send FD frames"));
+        }
+        let id = if frame.extended {
+            format!("T{:08X}", frame.id)
+        } else {
+            format!("t{:03X}", frame.id)
+        };
+        let data: String = frame.data.iter().map(|byte| format!("{:02X}", byte)).collect();
+        self.command(&format!("{}{}{}", id, frame.data.len(), data))
+    }
+}
+
+/// Parses `t1232DEAD` or `T123456782DEAD`, which may end in a timestamp. Remote
+/// frames (`r` and `R`) carry no values, and other lines are replies to commands.
+fn parse_line(line: &[u8]) -> Option<Frame> {
+    // Noise from the adapter isn't a frame, and would break slicing by byte below.
+    if !line.is_ascii() {
+        return None;
+    }
+    let line = std::str::from_utf8(line).ok()?;
+    let (id_length, extended, mask) = match line.get(..1)? {
+        "t" => (3, false, libc::CAN_SFF_MASK),
+        "T" => (8, true, libc::CAN_EFF_MASK),
+        _ => return None,
+    };
+    let id = u32::from_str_radix(line.get(1..1 + id_length)?, 16).ok()? & mask;
+    let length = usize::from_str_radix(line.get(1 + id_length..2 + id_length)?, 16).ok()?;
+    if length > 8 {
+        return None;
+    }
+    let data = line.get(2 + id_length..2 + id_length + 2 * length)?;
+    let data = (0..length)
+        .map(|i| u8::from_str_radix(&data[2 * i..2 * i + 2], 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+
+    Some(Frame { id, extended, data })
+}
+
+/// Puts the port in raw mode at the given baud rate, which USB adapters tend to ignore.
+fn set_raw(port: &File, baud: u32) -> io::Result<()> {
+    let speed = match baud {
+        9600 => libc::B9600,
+        19200 => libc::B19200,
+        38400 => libc::B38400,
+        57600 => libc::B57600,
+        115_200 => libc::B115200,
+        230_400 => libc::B230400,
+        460_800 => libc::B460800,
+        921_600 => libc::B921600,
+        1_000_000 => libc::B1000000,
+        2_000_000 => libc::B2000000,
+        3_000_000 => libc::B3000000,
+        _ => {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Unsupported baud rate {}", baud)))
+        }
+    };
+
+    let fd = port.as_raw_fd();
+    let mut termios: libc::termios = unsafe { mem::zeroed() };
+    if unsafe { libc::tcgetattr(fd, &mut termios) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    unsafe {
+        libc::cfmakeraw(&mut termios);
+        libc::cfsetispeed(&mut termios, speed);
+        libc::cfsetospeed(&mut termios, speed);
+    }
+    termios.c_cflag |= libc::CLOCAL | libc::CREAD;
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+    use std::os::unix::io::FromRawFd;
+
+    /// Opens a pseudo-terminal, returning its master side and the device name of its slave.
+    fn pseudo_terminal() -> (File, String) {
+        unsafe {
+            let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+            assert!(master >= 0, "{}", io::Error::last_os_error());
+            assert_eq!(libc::grantpt(master), 0);
+            assert_eq!(libc::unlockpt(master), 0);
+            let mut name = [0 as libc::c_char; 128];
+            assert_eq!(libc::ptsname_r(master, name.as_mut_ptr(), name.len()), 0);
+            let name = CStr::from_ptr(name.as_ptr()).to_str().unwrap().to_string();
+            (File::from_raw_fd(master), name)
+        }
+    }
+
+    fn read_exactly(master: &mut File, length: usize) -> Vec<u8> {
+        let mut received = vec![0; length];
+        master.read_exact(&mut received).unwrap();
+        received
+    }
+
+    #[test]
+    fn sets_bitrate_and_opens_channel() {
+        let (mut master, device) = pseudo_terminal();
+        let _slcan = Slcan::open(&device, 500_000, 115_200).unwrap();
+        assert_eq!(read_exactly(&mut master, 7), b"C\rS6\rO\r");
+    }
+
+    #[test]
+    fn reads_frames() {
+        let (mut master, device) = pseudo_terminal();
+        let mut slcan = Slcan::open(&device, 125_000, 115_200).unwrap();
+        read_exactly(&mut master, 7);
+
+        // Command replies, an error, a remote frame and noise are skipped, and frames may
+        // arrive split over reads.
+        master
+            .write_all(b"\rt1232DEAD\r\x07r7DF0\rt1232a\xC3\xA9b\rT18FEF1003AABBCC1234\rt7E")
+            .unwrap();
+        let timeout = Duration::from_millis(500);
+        let frame = slcan.read_frame(timeout).unwrap();
+        assert_eq!((frame.id, frame.extended, frame.data), (0x123, false, vec![0xDE, 0xAD]));
+        let frame = slcan.read_frame(timeout).unwrap();
+        assert_eq!((frame.id, frame.extended, frame.data), (0x18FE_F100, true, vec![0xAA, 0xBB, 0xCC]));
+
+        master.write_all(b"83410C1A\r").unwrap();
+        let frame = slcan.read_frame(timeout).unwrap();
+        assert_eq!((frame.id, frame.data), (0x7E8, vec![0x41, 0x0C, 0x1A]));
+
+        let error = slcan.read_frame(Duration::from_millis(20)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn masks_ids_to_their_width() {
+        let frame = parse_line(b"tFFF100").unwrap();
+        assert_eq!((frame.id, frame.extended), (0x7FF, false));
+        let frame = parse_line(b"TFFFFFFFF0").unwrap();
+        assert_eq!((frame.id, frame.extended), (0x1FFF_FFFF, true));
+    }
+
+    #[test]
+    fn waits_out_the_timeout_after_a_hangup() {
+        let (mut master, device) = pseudo_terminal();
+        let mut slcan = Slcan::open(&device, 500_000, 115_200).unwrap();
+        read_exactly(&mut master, 7);
+        drop(master);
+
+        let timeout = Duration::from_millis(50);
+        let start = Instant::now();
+        let error = slcan.read_frame(timeout).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+        assert!(start.elapsed() >= timeout);
+    }
+
+    #[test]
+    fn writes_frames() {
+        let (mut master, device) = pseudo_terminal();
+        let mut slcan = Slcan::open(&device, 1_000_000, 115_200).unwrap();
+        read_exactly(&mut master, 7);
+
+        let request = Frame {
+            id: 0x7DF,
+            extended: false,
+            data: vec![0x02, 0x01, 0x0C],
+        };
+        slcan.write_frame(&request).unwrap();
+        assert_eq!(read_exactly(&mut master, 12), b"t7DF302010C\r");
+
+        let extended = Frame {
+            id: 0x18DA_10F1,
+            extended: true,
+            data: vec![],
+        };
+        slcan.write_frame(&extended).unwrap();
+        assert_eq!(read_exactly(&mut master, 11), b"T18DA10F10\r");
+
+        let fd = Frame {
+            data: vec![0; 12],
+            ..request
+        };
+        assert!(slcan.write_frame(&fd).is_err());
+    }
+
+    #[test]
+    fn rejects_unsupported_bitrates() {
+        let (_master, device) = pseudo_terminal();
+        let error = Slcan::open(&device, 33_333, 115_200).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
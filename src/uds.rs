@@ -0,0 +1,217 @@
+use crate::can::{Frame, FrameFilter};
+use crate::config::UdsDid;
+use crate::decoder::Decoder;
+use crate::isotp::IsoTp;
+use crate::signal::{ByteOrder, Multiplexer, Signal, ValueType};
+use std::time::{Duration, Instant};
+
+const DIAGNOSTIC_SESSION_CONTROL: u8 = 0x10;
+const READ_DATA_BY_IDENTIFIER: u8 = 0x22;
+const TESTER_PRESENT: u8 = 0x3E;
+const SUPPRESS_RESPONSE: u8 = 0x80;
+const RESPONSE_OFFSET: u8 = 0x40;
+const NEGATIVE_RESPONSE: u8 = 0x7F;
+
+const RESPONSE_PENDING: u8 = 0x78;
+const SUBFUNCTION_NOT_SUPPORTED_IN_SESSION: u8 = 0x7E;
+const SERVICE_NOT_SUPPORTED_IN_SESSION: u8 = 0x7F;
+
+// P2 and P2*: how long the ECU may take to answer, and to answer after asking for more time.
+const RESPONSE_TIMEOUT: Duration = Duration::from_millis(150);
+const EXTENDED_RESPONSE_TIMEOUT: Duration = Duration::from_millis(5000);
+// Well within the ECU's 5 second session timeout (S3).
+const TESTER_PRESENT_INTERVAL: Duration = Duration::from_millis(2000);
+
+// Responses start with [0x62, DID high, DID low], followed by the data.
+const RESPONSE_HEADER_BITS: u16 = 24;
+
+/// Decodes `signal` from the data of a DID's positive responses. Slots and start bits
+/// count from the first byte after the DID.
+pub fn decoder(uds: &UdsDid, mut signal: Signal) -> Decoder {
+    let response = u64::from(READ_DATA_BY_IDENTIFIER + RESPONSE_OFFSET);
+    signal.start_bit += RESPONSE_HEADER_BITS;
+
+    Decoder {
+        filter: FrameFilter::exact(uds.response_id, uds.extended),
+        signal,
+        multiplexer: Some(Multiplexer {
+            signal: Signal {
+                start_bit: 7,
+                length: RESPONSE_HEADER_BITS,
+                byte_order: ByteOrder::BigEndian,
+                value_type: ValueType::Unsigned,
+                factor: 1.0,
+                offset: 0.0,
+            },
+            value: response << 16 | u64::from(uds.did),
+        }),
+        calibration: None,
+        raw: false,
+        j1939: false,
+    }
+}
+
+struct DidRequest {
+    did: u16,
+    interval: Duration,
+    next_due: Instant,
+}
+
+struct Pending {
+    service: u8,
+    deadline: Instant,
+}
+
+/// Polls data identifiers from one ECU, one request at a time, over an ISO-TP link.
+/// Enters a diagnostic session first if asked to, and keeps it alive with tester present.
+pub struct UdsClient {
+    link: IsoTp,
+    request_id: u32,
+    response_id: u32,
+    extended: bool,
+    session: Option<u8>,
+    in_session: bool,
+    next_tester_present: Instant,
+    requests: Vec<DidRequest>,
+    pending: Option<Pending>,
+}
+
+impl UdsClient {
+    pub fn new(request_id: u32, response_id: u32, extended: bool) -> UdsClient {
+        UdsClient {
+            link: IsoTp::new(request_id, response_id, extended),
+            request_id,
+            response_id,
+            extended,
+            session: None,
+            in_session: false,
+            next_tester_present: Instant::now(),
+            requests: Vec::new(),
+            pending: None,
+        }
+    }
+
+    /// Whether this client talks to the ECU a DID is read from.
+    pub fn serves(&self, uds: &UdsDid) -> bool {
+        self.request_id == uds.request_id
+            && self.response_id == uds.response_id
+            && self.extended == uds.extended
+    }
+
+    pub fn set_session(&mut self, session: u8) {
+        self.session = Some(session);
+    }
+
+    /// Reads `did` `rate` times per second.
+    pub fn add(&mut self, did: u16, rate: f32) {
+        if self.requests.iter().any(|r| r.did == did) {
+            return;
+        }
+        self.requests.push(DidRequest {
+            did,
+            interval: Duration::from_secs_f32(1.0 / rate.max(0.01)),
+            next_due: Instant::now(),
+        });
+    }
+
+    pub fn receives(&self, frame: &Frame) -> bool {
+        self.link.receives(frame)
+    }
+
+    /// The next frame to send, if any is due.
+    pub fn poll(&mut self, now: Instant) -> Option<Frame> {
+        if let Some(frame) = self.link.poll(now) {
+            return Some(frame);
+        }
+
+        if let Some(pending) = &self.pending {
+            if now < pending.deadline {
+                return None;
+            }
+            // Unanswered requests are sent again once they are due.
+            self.pending = None;
+        }
+
+        match self.session {
+            Some(session) if !self.in_session => {
+                self.request(vec![DIAGNOSTIC_SESSION_CONTROL, session], now);
+            }
+            Some(_) if now >= self.next_tester_present => {
+                self.next_tester_present = now + TESTER_PRESENT_INTERVAL;
+                let _ = self.link.send(vec![TESTER_PRESENT, SUPPRESS_RESPONSE]);
+            }
+            _ => {
+                let request = self
+                    .requests
+                    .iter_mut()
+                    .filter(|r| r.next_due <= now)
+                    .min_by_key(|r| r.next_due)?;
+                request.next_due = now + request.interval;
+                let [high, low] = request.did.to_be_bytes();
+                self.request(vec![READ_DATA_BY_IDENTIFIER, high, low], now);
+            }
+        }
+        self.link.poll(now)
+    }
+
+    /// Takes a frame from the response ID. Returns positive ReadDataByIdentifier
+    /// responses, as a frame holding the whole response, for the decoders.
+    pub fn handle(&mut self, frame: &Frame, now: Instant) -> Option<Frame> {
+        let response = match self.link.handle(frame, now)? {
+            Ok(response) => response,
+            Err(_) => {
+                self.pending = None;
+                return None;
+            }
+        };
+        let service = *response.first()?;
+
+        if service == NEGATIVE_RESPONSE {
+            let code = *response.get(2)?;
+            match code {
+                RESPONSE_PENDING => {
+                    if let Some(pending) = self.pending.as_mut() {
+                        pending.deadline = now + EXTENDED_RESPONSE_TIMEOUT;
+                    }
+                }
+                // The ECU fell back to the default session, so enter ours again.
+                SUBFUNCTION_NOT_SUPPORTED_IN_SESSION | SERVICE_NOT_SUPPORTED_IN_SESSION => {
+                    self.in_session = false;
+                    self.pending = None;
+                }
+                _ => self.pending = None,
+            }
+            return None;
+        }
+
+        if self
+            .pending
+            .as_ref()
+            .is_some_and(|p| service == p.service + RESPONSE_OFFSET)
+        {
+            self.pending = None;
+        }
+        match service.wrapping_sub(RESPONSE_OFFSET) {
+            DIAGNOSTIC_SESSION_CONTROL => {
+                self.in_session = true;
+                self.next_tester_present = now + TESTER_PRESENT_INTERVAL;
+                None
+            }
+            READ_DATA_BY_IDENTIFIER => Some(Frame {
+                id: frame.id,
+                extended: frame.extended,
+                data: response,
+            }),
+            _ => None,
+        }
+    }
+
+    fn request(&mut self, request: Vec<u8>, now: Instant) {
+        self.pending = Some(Pending {
+            service: request[0],
+            deadline: now + RESPONSE_TIMEOUT,
+        });
+        // Requests are a few bytes, so they always fit in a message.
+        let _ = self.link.send(request);
+    }
+}
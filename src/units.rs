@@ -0,0 +1,116 @@
+#[derive(Clone, Copy, PartialEq)]
+enum Quantity {
+    Pressure,
+    Temperature,
+    Speed,
+}
+
+/// A linear conversion from the unit on the wire to the displayed unit.
+#[derive(Clone, Copy)]
+pub struct Conversion {
+    factor: f32,
+    offset: f32,
+}
+
+impl Conversion {
+    pub fn identity() -> Conversion {
+        Conversion {
+            factor: 1.0,
+            offset: 0.0,
+        }
+    }
+
+    /// Returns `None` if the units measure different quantities. Units that aren't
+    /// known, e.g. "rpm" and "RPM", are taken as labels of the same unit.
+    pub fn between(from: &str, to: &str) -> Option<Conversion> {
+        let ((from_quantity, from_factor, from_offset), (to_quantity, to_factor, to_offset)) =
+            match (unit(from), unit(to)) {
+                (Some(from), Some(to)) => (from, to),
+                _ => return Some(Conversion::identity()),
+            };
+        if from_quantity != to_quantity {
+            return None;
+        }
+
+        // Go through the base unit of the quantity: base = value * factor + offset.
+        Some(Conversion {
+            factor: from_factor / to_factor,
+            offset: (from_offset - to_offset) / to_factor,
+        })
+    }
+
+    pub fn apply(&self, value: f32) -> f32 {
+        value * self.factor + self.offset
+    }
+
+    /// Converts a displayed value back to the unit on the wire.
+    pub fn invert(&self, value: f32) -> f32 {
+        (value - self.offset) / self.factor
+    }
+}
+
+/// The quantity of a unit, and how to convert it to the base unit of that quantity.
+fn unit(name: &str) -> Option<(Quantity, f32, f32)> {
+    let unit = match name {
+        "kPa" => (Quantity::Pressure, 1.0, 0.0),
+        "bar" => (Quantity::Pressure, 100.0, 0.0),
+        "psi" => (Quantity::Pressure, 6.894_757, 0.0),
+        "C" | "°C" | "degC" => (Quantity::Temperature, 1.0, 0.0),
+        "F" | "°F" | "degF" => (Quantity::Temperature, 5.0 / 9.0, -32.0 * 5.0 / 9.0),
+        "K" => (Quantity::Temperature, 1.0, -273.15),
+        "km/h" | "kph" => (Quantity::Speed, 1.0, 0.0),
+        "mph" => (Quantity::Speed, 1.609_344, 0.0),
+        "m/s" => (Quantity::Speed, 3.6, 0.0),
+        _ => return None,
+    };
+    Some(unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn converts_between_units_of_a_quantity() {
+        let kpa_to_bar = Conversion::between("kPa", "bar").unwrap();
+        assert!(close(kpa_to_bar.apply(250.0), 2.5));
+        let mph_to_kph = Conversion::between("mph", "km/h").unwrap();
+        assert!(close(mph_to_kph.apply(60.0), 96.560_64));
+    }
+
+    #[test]
+    fn converts_temperatures_with_their_offsets() {
+        let c_to_f = Conversion::between("°C", "F").unwrap();
+        assert!(close(c_to_f.apply(100.0), 212.0));
+        assert!(close(c_to_f.apply(-40.0), -40.0));
+        assert!(close(c_to_f.invert(32.0), 0.0));
+        let k_to_c = Conversion::between("K", "degC").unwrap();
+        assert!(close(k_to_c.apply(273.15), 0.0));
+    }
+
+    #[test]
+    fn inverts_conversions() {
+        let psi_to_bar = Conversion::between("psi", "bar").unwrap();
+        assert!(close(psi_to_bar.invert(psi_to_bar.apply(30.0)), 30.0));
+        let f_to_k = Conversion::between("F", "K").unwrap();
+        assert!(close(f_to_k.invert(f_to_k.apply(75.0)), 75.0));
+    }
+
+    #[test]
+    fn takes_unknown_units_as_labels() {
+        for (from, to) in [("rpm", "RPM"), ("%", "pct"), ("L/h", "l/h"), ("kPa", "kpa")].iter() {
+            let conversion = Conversion::between(from, to).unwrap();
+            assert_eq!(conversion.apply(42.0), 42.0);
+        }
+    }
+
+    #[test]
+    fn rejects_different_quantities() {
+        assert!(Conversion::between("kPa", "C").is_none());
+        assert!(Conversion::between("mph", "psi").is_none());
+    }
+}
@@ -0,0 +1,189 @@
+use crate::can::{Frame, FrameFilter};
+use crate::config::{ChecksumAlgorithm, FrameCheckConfig};
+use crate::signal::{ByteOrder, Signal, ValueType};
+
+struct Counter {
+    signal: Signal,
+    last: Option<u64>,
+}
+
+struct Checksum {
+    algorithm: ChecksumAlgorithm,
+    byte: usize,
+    polynomial: u8,
+    init: u8,
+    xor_out: u8,
+}
+
+/// Checks the alive counter and checksum of the frames matching its filter, and
+/// counts the frames that fail.
+pub struct FrameCheck {
+    pub filter: FrameFilter,
+    pub name: Option<String>, // Channel that counts the failed frames
+    pub failures: u32,
+    counter: Option<Counter>,
+    checksum: Option<Checksum>,
+}
+
+impl FrameCheck {
+    pub fn new(config: &FrameCheckConfig) -> FrameCheck {
+        FrameCheck {
+            filter: FrameFilter::exact(
+                config.frame_id,
+                config.extended.unwrap_or(config.frame_id > 0x7FF),
+            ),
+            name: config.name.clone(),
+            failures: 0,
+            counter: config.counter.as_ref().map(|counter| Counter {
+                signal: Signal {
+                    start_bit: counter.start_bit,
+                    length: counter.bit_length,
+                    byte_order: counter.byte_order.unwrap_or(ByteOrder::BigEndian),
+                    value_type: ValueType::Unsigned,
+                    factor: 1.0,
+                    offset: 0.0,
+                },
+                last: None,
+            }),
+            checksum: config.checksum.as_ref().map(|checksum| Checksum {
+                algorithm: checksum.algorithm,
+                byte: usize::from(checksum.byte),
+                // SAE J1850 unless configured otherwise.
+                polynomial: checksum.polynomial.unwrap_or(0x1D),
+                init: checksum.init.unwrap_or(0xFF),
+                xor_out: checksum.xor_out.unwrap_or(0xFF),
+            }),
+        }
+    }
+
+    /// Whether the frame passes. Frames too short to hold the counter or checksum fail.
+    pub fn check(&mut self, frame: &Frame) -> bool {
+        // Both are checked, so the counter follows frames with a bad checksum too.
+        let checksum_passed = self.check_checksum(frame);
+        let counter_passed = self.check_counter(frame);
+        let passed = checksum_passed && counter_passed;
+        if !passed {
+            self.failures = self.failures.saturating_add(1);
+        }
+        passed
+    }
+
+    fn check_checksum(&self, frame: &Frame) -> bool {
+        let checksum = match &self.checksum {
+            Some(checksum) => checksum,
+            None => return true,
+        };
+        let received = match frame.data.get(checksum.byte) {
+            Some(received) => *received,
+            None => return false,
+        };
+        let data = frame
+            .data
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != checksum.byte)
+            .map(|(_, byte)| *byte);
+
+        let expected = match checksum.algorithm {
+            ChecksumAlgorithm::Xor => data.fold(0, |xor, byte| xor ^ byte),
+            ChecksumAlgorithm::Crc8 => {
+                let crc = data.fold(checksum.init, |crc, byte| crc8(crc, byte, checksum.polynomial));
+                crc ^ checksum.xor_out
+            }
+        };
+        expected == received
+    }
+
+    fn check_counter(&mut self, frame: &Frame) -> bool {
+        let counter = match self.counter.as_mut() {
+            Some(counter) => counter,
+            None => return true,
+        };
+        let received = match counter.signal.raw(&frame.data) {
+            Ok(received) => received,
+            Err(_) => return false,
+        };
+
+        // Start over from whatever arrives after a gap, so one bad frame isn't followed by more.
+        let last = counter.last.replace(received);
+        let modulus = 1u64.checked_shl(u32::from(counter.signal.length)).unwrap_or(0);
+        match last {
+            Some(last) if modulus == 0 => received == last.wrapping_add(1),
+            Some(last) => received == (last + 1) % modulus,
+            None => true,
+        }
+    }
+}
+
+fn crc8(crc: u8, byte: u8, polynomial: u8) -> u8 {
+    let mut crc = crc ^ byte;
+    for _ in 0..8 {
+        crc = if crc & 0x80 != 0 {
+            (crc << 1) ^ polynomial
+        } else {
+            crc << 1
+        };
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ChecksumConfig, CounterConfig};
+
+    fn frame_check() -> FrameCheck {
+        FrameCheck::new(&FrameCheckConfig {
+            frame_id: 0x100,
+            extended: None,
+            name: None,
+            counter: Some(CounterConfig {
+                start_bit: 0,
+                bit_length: 4,
+                byte_order: Some(ByteOrder::LittleEndian),
+            }),
+            checksum: Some(ChecksumConfig {
+                algorithm: ChecksumAlgorithm::Xor,
+                byte: 1,
+                polynomial: None,
+                init: None,
+                xor_out: None,
+            }),
+        })
+    }
+
+    fn frame(counter: u8, checksum_error: u8) -> Frame {
+        Frame {
+            id: 0x100,
+            extended: false,
+            data: vec![counter, counter ^ 0x42 ^ checksum_error, 0x42],
+        }
+    }
+
+    #[test]
+    fn counts_failed_frames() {
+        let mut check = frame_check();
+        assert!(check.check(&frame(0, 0)));
+        assert!(check.check(&frame(1, 0)));
+        assert!(!check.check(&frame(3, 0)), "skipped a counter value");
+        assert!(check.check(&frame(4, 0)));
+        assert!(!check.check(&frame(15, 0)));
+        assert!(check.check(&frame(0, 0)), "counter wraps");
+        assert_eq!(check.failures, 2);
+    }
+
+    #[test]
+    fn bad_checksum_fails_only_its_own_frame() {
+        let mut check = frame_check();
+        assert!(check.check(&frame(0, 0)));
+        assert!(!check.check(&frame(1, 0xFF)));
+        assert!(check.check(&frame(2, 0)));
+        assert_eq!(check.failures, 1);
+    }
+
+    #[test]
+    fn crc8_matches_sae_j1850() {
+        let crc = b"123456789".iter().fold(0xFF, |crc, byte| crc8(crc, *byte, 0x1D)) ^ 0xFF;
+        assert_eq!(crc, 0x4B);
+    }
+}